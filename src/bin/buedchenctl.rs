@@ -0,0 +1,404 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use buedchen::ipc::{self, Event, OutputInfo, Request, Response, ToplevelInfo};
+use clap::{Parser, Subcommand, ValueEnum};
+
+/// Control a running buedchen compositor
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Control socket, defaults to $BUEDCHEN_SOCKET or the socket of the only running compositor
+    #[arg(long, global = true)]
+    socket: Option<PathBuf>,
+    /// Print the responses and events as JSON lines instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the outputs
+    Outputs,
+    /// List the toplevel windows
+    Toplevels,
+    /// Write the content of an output as PNG file
+    Screenshot {
+        path: PathBuf,
+        /// Output to capture, defaults to the first one
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Restart the kiosk client, or the application on an output
+    RestartClient {
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Turn the display of an output on or off
+    Dpms { state: PowerState, output: String },
+    /// Change gamma, brightness or colour temperature of an output
+    Color {
+        output: String,
+        /// Exponent of the gamma curve, 1.0 is neutral
+        #[arg(long)]
+        gamma: Option<f32>,
+        /// Multiplier between 0.0 and 1.0
+        #[arg(long)]
+        brightness: Option<f32>,
+        /// Colour temperature of white in Kelvin, 6500 is neutral
+        #[arg(long)]
+        temperature: Option<u32>,
+    },
+    /// Switch to one of the configured keyboard layouts, e.g. `de` or `ch(fr)`
+    KeyboardLayout { layout: String },
+    /// Override the opening hours schedule
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+    /// Read the configuration file again
+    Reload,
+    /// Stop the compositor
+    Quit,
+    /// Print events until interrupted
+    Events,
+}
+
+#[derive(Subcommand, Debug)]
+enum ScheduleAction {
+    /// Keep all outputs on, e.g. `force-on 2h`
+    ForceOn {
+        #[arg(value_parser = parse_duration)]
+        duration: u64,
+    },
+    /// Keep all outputs off, e.g. `force-off 30m`
+    ForceOff {
+        #[arg(value_parser = parse_duration)]
+        duration: u64,
+    },
+    /// Follow the schedule again
+    Resume,
+}
+
+/// Seconds in a duration like `90`, `90s`, `30m` or `2h`
+fn parse_duration(s: &str) -> Result<u64, String> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
+    };
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("expected a duration like 30m or 2h, got {}", s))?;
+    let factor = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("unknown unit {} in {}, use s, m, h or d", unit, s)),
+    };
+    number
+        .checked_mul(factor)
+        .ok_or_else(|| format!("duration {} is too long", s))
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PowerState {
+    On,
+    Off,
+}
+
+/// Control socket given by the environment, or the only one in the runtime directory
+fn find_socket() -> Result<PathBuf, String> {
+    if let Some(path) = env::var_os(ipc::SOCKET_ENV) {
+        return Ok(path.into());
+    }
+    if let Some(path) = env::var("WAYLAND_DISPLAY")
+        .ok()
+        .and_then(|display| ipc::socket_path(&display))
+        .filter(|path| path.exists())
+    {
+        return Ok(path);
+    }
+
+    let runtime_dir =
+        env::var_os("XDG_RUNTIME_DIR").ok_or("XDG_RUNTIME_DIR is not set, pass --socket")?;
+    let mut sockets = fs::read_dir(&runtime_dir)
+        .map_err(|err| format!("Couldn't read {:?}: {}", runtime_dir, err))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| {
+                    name.starts_with("buedchen-") && name.ends_with(".sock")
+                })
+        })
+        .collect::<Vec<_>>();
+    match sockets.len() {
+        0 => Err("No running compositor found, pass --socket".into()),
+        1 => Ok(sockets.remove(0)),
+        _ => Err(format!(
+            "Several compositors are running, pass one of {:?} with --socket",
+            sockets
+        )),
+    }
+}
+
+struct Connection {
+    stream: UnixStream,
+    reader: BufReader<UnixStream>,
+}
+
+impl Connection {
+    fn open(path: &Path) -> io::Result<Connection> {
+        let stream = UnixStream::connect(path)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Connection { stream, reader })
+    }
+
+    /// Next line sent by the compositor, `None` once the connection was closed
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line))
+    }
+
+    fn request(&mut self, request: &Request) -> io::Result<(String, Response)> {
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        self.stream.write_all(&line)?;
+
+        let line = self.read_line()?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the compositor closed the connection",
+            )
+        })?;
+        let response = serde_json::from_str(&line)?;
+        Ok((line, response))
+    }
+}
+
+/// Print rows with every column padded to its widest cell
+fn print_table(header: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = header.iter().map(|cell| cell.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = header.iter().map(|cell| cell.to_string()).collect();
+    for row in std::iter::once(header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+fn print_outputs(outputs: &[OutputInfo]) {
+    print_table(
+        &[
+            "NAME",
+            "MODE",
+            "POSITION",
+            "SCALE",
+            "TRANSFORM",
+            "POWER",
+            "MODEL",
+        ],
+        outputs
+            .iter()
+            .map(|output| {
+                vec![
+                    output.name.clone(),
+                    output.mode.map_or_else(
+                        || "-".into(),
+                        |mode| {
+                            format!(
+                                "{}x{}@{:.3}",
+                                mode.width,
+                                mode.height,
+                                mode.refresh as f64 / 1000.0
+                            )
+                        },
+                    ),
+                    format!("{},{}", output.position.0, output.position.1),
+                    output.scale.to_string(),
+                    output.transform.clone(),
+                    if output.powered { "on" } else { "off" }.into(),
+                    format!("{} {}", output.make, output.model),
+                ]
+            })
+            .collect(),
+    );
+}
+
+fn print_toplevels(toplevels: &[ToplevelInfo]) {
+    print_table(
+        &["APP ID", "TITLE", "PID", "OUTPUT", "FOCUSED"],
+        toplevels
+            .iter()
+            .map(|toplevel| {
+                vec![
+                    toplevel.app_id.clone().unwrap_or_else(|| "-".into()),
+                    toplevel.title.clone().unwrap_or_else(|| "-".into()),
+                    toplevel
+                        .pid
+                        .map_or_else(|| "-".into(), |pid| pid.to_string()),
+                    toplevel.output.clone().unwrap_or_else(|| "-".into()),
+                    if toplevel.focused { "yes" } else { "no" }.into(),
+                ]
+            })
+            .collect(),
+    );
+}
+
+fn print_reloaded(changed: &[String], not_applied: &[String]) {
+    if changed.is_empty() {
+        println!("Nothing changed");
+        return;
+    }
+    println!("Changed: {}", changed.join(", "));
+    if !not_applied.is_empty() {
+        println!("Not applied: {}", not_applied.join(", "));
+    }
+}
+
+fn describe_event(event: &Event) -> String {
+    match event {
+        Event::ClientExited { pid, output } => format!(
+            "client-exited pid={} output={}",
+            pid.map_or_else(|| "-".into(), |pid| pid.to_string()),
+            output.as_deref().unwrap_or("-")
+        ),
+        Event::OutputAdded { name } => format!("output-added {}", name),
+        Event::OutputRemoved { name } => format!("output-removed {}", name),
+        Event::FocusChanged { toplevel: None } => "focus-changed none".into(),
+        Event::FocusChanged {
+            toplevel: Some(toplevel),
+        } => format!(
+            "focus-changed app_id={} title={:?} pid={} output={}",
+            toplevel.app_id.as_deref().unwrap_or("-"),
+            toplevel.title.as_deref().unwrap_or(""),
+            toplevel
+                .pid
+                .map_or_else(|| "-".into(), |pid| pid.to_string()),
+            toplevel.output.as_deref().unwrap_or("-")
+        ),
+    }
+}
+
+fn run(cli: Cli) -> Result<(), String> {
+    let path = match cli.socket {
+        Some(path) => path,
+        None => find_socket()?,
+    };
+    let mut connection = Connection::open(&path)
+        .map_err(|err| format!("Couldn't connect to {:?}: {}", path, err))?;
+
+    let request = match cli.command {
+        Command::Outputs => Request::Outputs,
+        Command::Toplevels => Request::Toplevels,
+        Command::Screenshot { path, output } => Request::Screenshot {
+            output,
+            // the compositor doesn't share our working directory
+            path: env::current_dir()
+                .map_err(|err| format!("Couldn't get the working directory: {}", err))?
+                .join(path),
+        },
+        Command::RestartClient { output } => Request::RestartClient { output },
+        Command::Dpms { state, output } => Request::SetOutputPower {
+            output,
+            on: matches!(state, PowerState::On),
+        },
+        Command::Color {
+            output,
+            gamma,
+            brightness,
+            temperature,
+        } => Request::SetOutputColor {
+            output,
+            gamma,
+            brightness,
+            temperature,
+        },
+        Command::KeyboardLayout { layout } => Request::SetKeyboardLayout { layout },
+        Command::Schedule { action } => match action {
+            ScheduleAction::ForceOn { duration } => {
+                Request::OverrideSchedule { on: true, duration }
+            }
+            ScheduleAction::ForceOff { duration } => Request::OverrideSchedule {
+                on: false,
+                duration,
+            },
+            ScheduleAction::Resume => Request::ClearScheduleOverride,
+        },
+        Command::Reload => Request::Reload,
+        Command::Quit => Request::Quit,
+        Command::Events => Request::Subscribe,
+    };
+
+    let (line, response) = connection
+        .request(&request)
+        .map_err(|err| format!("Request failed: {}", err))?;
+    if let Response::Error { message } = &response {
+        return Err(message.clone());
+    }
+    if cli.json {
+        if request != Request::Subscribe {
+            print!("{}", line);
+        }
+    } else {
+        match &response {
+            Response::Outputs { outputs } => print_outputs(outputs),
+            Response::Toplevels { toplevels } => print_toplevels(toplevels),
+            Response::Reloaded {
+                changed,
+                not_applied,
+            } => print_reloaded(changed, not_applied),
+            Response::Ok | Response::Error { .. } => {}
+        }
+    }
+
+    if request == Request::Subscribe {
+        while let Some(line) = connection
+            .read_line()
+            .map_err(|err| format!("Failed to read event: {}", err))?
+        {
+            if cli.json {
+                print!("{}", line);
+            } else {
+                match serde_json::from_str::<Event>(&line) {
+                    Ok(event) => println!("{}", describe_event(&event)),
+                    Err(err) => eprintln!("Unknown event {}: {}", line.trim_end(), err),
+                }
+            }
+            let _ = io::stdout().flush();
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("buedchenctl: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
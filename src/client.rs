@@ -1,7 +1,31 @@
 use std::{
-    io::{self},
-    process::{Command, ExitStatus, Stdio},
+    cell::RefCell,
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::io::AsFd,
+    path::PathBuf,
+    process::{ChildStderr, ChildStdout, Command, ExitStatus, Stdio},
+    rc::Rc,
+    sync::atomic::Ordering,
     thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use rustix::process::{kill_process, Pid, Signal};
+use smithay::reexports::{
+    calloop::{
+        generic::Generic,
+        timer::{TimeoutAction, Timer},
+        Interest, Mode, PostAction,
+    },
+    wayland_server::{protocol::wl_surface::WlSurface, Resource},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    ipc::Event,
+    state::{Backend, BuedchenState},
 };
 
 pub enum ClientStartError {
@@ -24,10 +48,15 @@ impl std::fmt::Display for ClientStartError {
     }
 }
 
-pub fn run_client(
-    args: &[String],
-    socket_name: &str,
-) -> Result<JoinHandle<Result<ExitStatus, io::Error>>, ClientStartError> {
+/// A freshly spawned client process
+pub struct ClientProcess {
+    pub pid: u32,
+    pub stdout: ChildStdout,
+    pub stderr: ChildStderr,
+    pub exit: JoinHandle<Result<ExitStatus, io::Error>>,
+}
+
+pub fn run_client(args: &[String], socket_name: &str) -> Result<ClientProcess, ClientStartError> {
     let mut args_iter = args.iter();
     let command = args_iter
         .next()
@@ -39,6 +68,721 @@ pub fn run_client(
         .stderr(Stdio::piped())
         .spawn()?;
 
+    let pid = child.id();
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+
     // Spawn a thread to wait for the child process to exit
-    Ok(thread::spawn(move || child.wait()))
+    Ok(ClientProcess {
+        pid,
+        stdout,
+        stderr,
+        exit: thread::spawn(move || child.wait()),
+    })
+}
+
+/// Time a client gets to exit after being asked to terminate, before it is killed
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Lines longer than this are split, so a client never writing a newline can't exhaust our memory
+const MAX_LINE_LENGTH: usize = 16 * 1024;
+
+/// Length of `bytes` without a UTF-8 sequence that is cut off at the end
+fn complete_utf8_len(bytes: &[u8]) -> usize {
+    // a character is at most 4 bytes long, find where the last one starts
+    for back in 1..=bytes.len().min(4) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xc0 == 0x80 {
+            continue;
+        }
+        let width = match byte {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        return if width > back {
+            bytes.len() - back
+        } else {
+            bytes.len()
+        };
+    }
+    bytes.len()
+}
+
+/// Size-rotated log file the client output is copied into
+///
+/// Once `path` would grow beyond `max_size` it is renamed to `path.1`, `path.1` to `path.2` and
+/// so on, keeping at most `max_files` rotated files.
+#[derive(Debug)]
+pub struct ClientLog {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: Option<File>,
+    size: u64,
+}
+
+impl ClientLog {
+    pub fn new(path: PathBuf, max_size: u64, max_files: usize) -> ClientLog {
+        ClientLog {
+            path,
+            max_size,
+            max_files,
+            file: None,
+            size: 0,
+        }
+    }
+
+    fn write_line(&mut self, pid: u32, stream: &str, line: &str) -> io::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let entry = format!(
+            "{}.{:03} [{} {}] {}\n",
+            timestamp.as_secs(),
+            timestamp.subsec_millis(),
+            pid,
+            stream,
+            line
+        );
+
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        if self.size > 0 && self.size + entry.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.as_mut().unwrap().write_all(entry.as_bytes())?;
+        self.size += entry.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        for index in (1..self.max_files).rev() {
+            match fs::rename(self.rotated_path(index), self.rotated_path(index + 1)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = Some(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&self.path)?,
+        );
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+}
+
+/// When the kiosk client should be restarted after it exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RestartMode {
+    /// Restart the client whenever it exits
+    Always,
+    /// Only restart the client if it exited with a non-zero exit code or was killed
+    OnFailure,
+    /// Never restart the client
+    Never,
+}
+
+/// What to do once the client will not be restarted anymore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GiveUpAction {
+    /// Stop the compositor
+    Exit,
+    /// Keep the compositor running and show the empty background
+    Fallback,
+}
+
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    pub mode: RestartMode,
+    /// Delay before the first restart, doubled on every consecutive restart
+    pub initial_backoff: Duration,
+    /// Upper bound for the restart delay
+    pub max_backoff: Duration,
+    /// Maximum number of restarts within `window` before giving up
+    pub max_restarts: u32,
+    pub window: Duration,
+    pub give_up: GiveUpAction,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            mode: RestartMode::Never,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_restarts: 5,
+            window: Duration::from_secs(60),
+            give_up: GiveUpAction::Exit,
+        }
+    }
+}
+
+/// Decision of the [`ClientSupervisor`] after the client exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// Start the client again after the given delay
+    Restart(Duration),
+    /// The client will not be started again
+    GiveUp(GiveUpAction),
+    /// The client was stopped on request and stays stopped until it is started again
+    Stopped,
+}
+
+/// Keeps track of the kiosk client process and applies the [`RestartPolicy`]
+#[derive(Debug)]
+pub struct ClientSupervisor {
+    args: Vec<String>,
+    policy: RestartPolicy,
+    log: Option<Rc<RefCell<ClientLog>>>,
+    child: Option<JoinHandle<Result<ExitStatus, io::Error>>>,
+    pid: Option<u32>,
+    started_at: Option<Instant>,
+    restarts: VecDeque<Instant>,
+    backoff: Duration,
+    /// The client was stopped on request and is started again right away
+    restart_requested: bool,
+    /// The client was stopped on request and is not restarted
+    stopped: bool,
+}
+
+impl ClientSupervisor {
+    pub fn new(
+        args: Vec<String>,
+        policy: RestartPolicy,
+        log: Option<ClientLog>,
+    ) -> ClientSupervisor {
+        let backoff = policy.initial_backoff;
+        ClientSupervisor {
+            args,
+            policy,
+            log: log.map(|log| Rc::new(RefCell::new(log))),
+            child: None,
+            pid: None,
+            started_at: None,
+            restarts: VecDeque::new(),
+            backoff,
+            restart_requested: false,
+            stopped: false,
+        }
+    }
+
+    /// A supervisor for another command, sharing restart policy and log with this one
+    ///
+    /// Only what happens once the command is given up on differs.
+    pub fn with_command(&self, args: Vec<String>, give_up: GiveUpAction) -> ClientSupervisor {
+        let backoff = self.policy.initial_backoff;
+        ClientSupervisor {
+            args,
+            policy: RestartPolicy {
+                give_up,
+                ..self.policy.clone()
+            },
+            log: self.log.clone(),
+            child: None,
+            pid: None,
+            started_at: None,
+            restarts: VecDeque::new(),
+            backoff,
+            restart_requested: false,
+            stopped: false,
+        }
+    }
+
+    /// Whether a command was given at all
+    pub fn has_command(&self) -> bool {
+        !self.args.is_empty()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.child.is_some()
+    }
+
+    /// Whether the client was stopped on request and should not be started
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Pid of the currently running client
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// Spawn the client, returning its output pipes
+    pub fn start(
+        &mut self,
+        socket_name: &str,
+    ) -> Result<(ChildStdout, ChildStderr), ClientStartError> {
+        let ClientProcess {
+            pid,
+            stdout,
+            stderr,
+            exit,
+        } = run_client(&self.args, socket_name)?;
+        self.child = Some(exit);
+        self.pid = Some(pid);
+        self.started_at = Some(Instant::now());
+        self.stopped = false;
+        Ok((stdout, stderr))
+    }
+
+    /// Check whether the client has exited and decide what should happen next
+    pub fn poll(&mut self) -> Option<ClientEvent> {
+        if !self.child.as_ref()?.is_finished() {
+            return None;
+        }
+
+        self.pid = None;
+        let success = match self.child.take().unwrap().join() {
+            Ok(Ok(exit_code)) => {
+                if exit_code.success() {
+                    info!("client exited normally");
+                } else {
+                    error!("client exited abnormally with code: {}", exit_code);
+                }
+                exit_code.success()
+            }
+            Ok(Err(e)) => {
+                error!(
+                    "client exited abnormally and we couldn't get an exit code: {:?}",
+                    e
+                );
+                false
+            }
+            Err(e) => {
+                error!("Couldn't join client thread: {:?}", e);
+                false
+            }
+        };
+
+        if self.stopped {
+            return Some(ClientEvent::Stopped);
+        }
+        if self.restart_requested {
+            self.restart_requested = false;
+            return Some(ClientEvent::Restart(Duration::ZERO));
+        }
+        Some(self.exited(success))
+    }
+
+    /// Ask the running client to terminate, it is started again once it exited
+    ///
+    /// The restart is not subject to the restart policy.
+    pub fn request_restart(&mut self) -> io::Result<()> {
+        let Some(pid) = self.pid.and_then(|pid| Pid::from_raw(pid as i32)) else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the client is not running",
+            ));
+        };
+        kill_process(pid, Signal::Term)?;
+        self.restart_requested = true;
+        Ok(())
+    }
+
+    /// Ask the running client to terminate and keep it from being restarted
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.stopped = true;
+        self.restart_requested = false;
+        match self.pid.and_then(|pid| Pid::from_raw(pid as i32)) {
+            Some(pid) => kill_process(pid, Signal::Term).map_err(Into::into),
+            None => Ok(()),
+        }
+    }
+
+    /// Kill the client if it is still the process `pid`, returns whether it was killed
+    pub fn kill(&self, pid: u32) -> io::Result<bool> {
+        if self.pid != Some(pid)
+            || self
+                .child
+                .as_ref()
+                .map_or(true, |child| child.is_finished())
+        {
+            return Ok(false);
+        }
+        let Some(pid) = Pid::from_raw(pid as i32) else {
+            return Ok(false);
+        };
+        kill_process(pid, Signal::Kill)?;
+        Ok(true)
+    }
+
+    /// Undo [`ClientSupervisor::stop`], returns whether the client has to be started again
+    ///
+    /// A client that is still terminating is started again once it exited.
+    pub fn resume(&mut self) -> bool {
+        if !std::mem::take(&mut self.stopped) {
+            return false;
+        }
+        if self.is_running() {
+            self.restart_requested = true;
+            return false;
+        }
+        true
+    }
+
+    /// Apply the restart policy after the client exited or failed to start
+    pub fn exited(&mut self, success: bool) -> ClientEvent {
+        let restart = match self.policy.mode {
+            RestartMode::Always => true,
+            RestartMode::OnFailure => !success,
+            RestartMode::Never => false,
+        };
+        if !restart {
+            return ClientEvent::GiveUp(self.policy.give_up);
+        }
+
+        let now = Instant::now();
+        // a client that ran stable for a whole window starts over with the initial delay
+        if self
+            .started_at
+            .take()
+            .map_or(false, |started_at| now - started_at >= self.policy.window)
+        {
+            self.backoff = self.policy.initial_backoff;
+        }
+
+        while self
+            .restarts
+            .front()
+            .map_or(false, |restart| now - *restart > self.policy.window)
+        {
+            self.restarts.pop_front();
+        }
+        if self.restarts.len() >= self.policy.max_restarts as usize {
+            warn!(
+                restarts = self.restarts.len(),
+                window = ?self.policy.window,
+                "client restarted too often, giving up"
+            );
+            return ClientEvent::GiveUp(self.policy.give_up);
+        }
+        self.restarts.push_back(now);
+
+        let delay = self.backoff;
+        self.backoff = (self.backoff * 2).min(self.policy.max_backoff);
+        ClientEvent::Restart(delay)
+    }
+}
+
+/// An application from the configuration, its toplevels are pinned to `output`
+#[derive(Debug)]
+pub struct KioskApp {
+    /// Connector name of the output
+    pub output: String,
+    /// App id of toplevels belonging to the application even if opened by another process
+    pub app_id: Option<String>,
+    pub supervisor: ClientSupervisor,
+}
+
+/// One of the supervised client processes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientSlot {
+    /// The client given on the command line
+    Main,
+    /// The application at this index of [`BuedchenState::apps`]
+    App(usize),
+}
+
+impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
+    /// Start the command line client and all configured applications
+    ///
+    /// Only a failing command line client is returned as an error, applications failing to start
+    /// are handled by their restart policy.
+    pub fn start_client(&mut self) -> Result<(), ClientStartError> {
+        if self.client.has_command() && !self.client.is_stopped() {
+            self.start_slot(ClientSlot::Main)?;
+        }
+        for index in 0..self.apps.len() {
+            if self.apps[index].supervisor.is_stopped() {
+                continue;
+            }
+            if let Err(err) = self.start_slot(ClientSlot::App(index)) {
+                error!(
+                    output = self.apps[index].output,
+                    "Couldn't start application: {}", err
+                );
+                let event = self.apps[index].supervisor.exited(false);
+                self.handle_client_event(ClientSlot::App(index), event);
+            }
+        }
+        Ok(())
+    }
+
+    fn supervisor_mut(&mut self, slot: ClientSlot) -> &mut ClientSupervisor {
+        match slot {
+            ClientSlot::Main => &mut self.client,
+            ClientSlot::App(index) => &mut self.apps[index].supervisor,
+        }
+    }
+
+    fn start_slot(&mut self, slot: ClientSlot) -> Result<(), ClientStartError> {
+        let socket_name = self
+            .socket_name
+            .clone()
+            .expect("WAYLAND_DISPLAY was not set yet by compositor");
+        let supervisor = self.supervisor_mut(slot);
+        let (stdout, stderr) = supervisor.start(&socket_name)?;
+        let pid = supervisor.pid().unwrap();
+        self.forward_client_output(pid, "stdout", stdout);
+        self.forward_client_output(pid, "stderr", stderr);
+        Ok(())
+    }
+
+    /// Pid of the process owning a surface
+    pub fn client_pid(&self, surface: &WlSurface) -> Option<u32> {
+        let client = surface.client()?;
+        let credentials = client.get_credentials(&self.display_handle).ok()?;
+        Some(credentials.pid as u32)
+    }
+
+    /// Output name a toplevel of the given client process and app id is pinned to
+    pub fn pinned_output(&self, pid: Option<u32>, app_id: Option<&str>) -> Option<String> {
+        self.apps
+            .iter()
+            .find(|app| {
+                (pid.is_some() && app.supervisor.pid() == pid)
+                    || (app_id.is_some() && app.app_id.as_deref() == app_id)
+            })
+            .map(|app| app.output.clone())
+    }
+
+    /// Re-emit everything the client writes to `pipe` as tracing events
+    fn forward_client_output<F>(&self, pid: u32, stream: &'static str, pipe: F)
+    where
+        F: AsFd + Read + 'static,
+    {
+        let log = self.client.log.clone();
+        let emit = move |line: &[u8]| {
+            let line = String::from_utf8_lossy(line);
+            let line = line.trim_end_matches('\r');
+            info!(target: "buedchen::client", pid, stream, "{}", line);
+            if let Some(log) = log.as_ref() {
+                if let Err(err) = log.borrow_mut().write_line(pid, stream, line) {
+                    warn!(?err, "Failed to write client log");
+                }
+            }
+        };
+
+        let mut buffer = Vec::new();
+        let mut chunk = vec![0u8; 4096];
+        let res = self.handle.insert_source(
+            Generic::new(pipe, Interest::READ, Mode::Level),
+            move |_, pipe, _| {
+                // Safety: we don't drop the pipe
+                let read = match unsafe { pipe.get_mut() }.read(&mut chunk) {
+                    Ok(read) => read,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(err) => {
+                        warn!(pid, stream, ?err, "Failed to read client output");
+                        0
+                    }
+                };
+
+                if read == 0 {
+                    if !buffer.is_empty() {
+                        emit(&buffer);
+                    }
+                    return Ok(PostAction::Remove);
+                }
+
+                buffer.extend_from_slice(&chunk[..read]);
+                while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                    emit(&buffer[..end]);
+                    buffer.drain(..=end);
+                }
+                if buffer.len() >= MAX_LINE_LENGTH {
+                    // keep a character cut off by the read for the next part of the line
+                    let end = complete_utf8_len(&buffer);
+                    emit(&buffer[..end]);
+                    buffer.drain(..end);
+                }
+                Ok(PostAction::Continue)
+            },
+        );
+        if let Err(err) = res {
+            warn!(pid, stream, ?err, "Failed to forward client output");
+        }
+    }
+
+    /// Restart or give up on the clients once they exited, called from the main loop
+    pub fn check_client(&mut self) {
+        for slot in self.slots().collect::<Vec<_>>() {
+            let supervisor = self.supervisor_mut(slot);
+            let pid = supervisor.pid();
+            if let Some(event) = supervisor.poll() {
+                let output = match slot {
+                    ClientSlot::Main => None,
+                    ClientSlot::App(index) => Some(self.apps[index].output.clone()),
+                };
+                self.emit_ipc_event(Event::ClientExited { pid, output });
+                self.handle_client_event(slot, event);
+            }
+        }
+    }
+
+    fn slots(&self) -> impl Iterator<Item = ClientSlot> {
+        std::iter::once(ClientSlot::Main).chain((0..self.apps.len()).map(ClientSlot::App))
+    }
+
+    /// Stop all clients until [`BuedchenState::resume_clients`] is called
+    pub fn stop_clients(&mut self) {
+        for slot in self.slots().collect::<Vec<_>>() {
+            let supervisor = self.supervisor_mut(slot);
+            if !supervisor.has_command() {
+                continue;
+            }
+            let pid = supervisor.pid();
+            match supervisor.stop() {
+                Ok(()) => {
+                    if let Some(pid) = pid {
+                        self.kill_after_timeout(slot, pid);
+                    }
+                }
+                Err(err) => warn!(?slot, "Couldn't stop the client: {}", err),
+            }
+        }
+    }
+
+    /// Start the clients stopped by [`BuedchenState::stop_clients`] again
+    pub fn resume_clients(&mut self) {
+        for slot in self.slots().collect::<Vec<_>>() {
+            if !self.supervisor_mut(slot).resume() {
+                continue;
+            }
+            if let Err(err) = self.start_slot(slot) {
+                error!(?slot, "Couldn't start client: {}", err);
+                let event = self.supervisor_mut(slot).exited(false);
+                self.handle_client_event(slot, event);
+            }
+        }
+    }
+
+    /// Slot of the command line client, or of the application on `output`
+    pub fn client_slot(&self, output: Option<&str>) -> Result<ClientSlot, String> {
+        let Some(output) = output else {
+            return Ok(ClientSlot::Main);
+        };
+        self.apps
+            .iter()
+            .position(|app| app.output == output)
+            .map(ClientSlot::App)
+            .ok_or_else(|| format!("No application on output {}", output))
+    }
+
+    /// Start a helper command that isn't supervised, its output goes to our own
+    pub fn spawn_helper(&self, args: &[String]) -> Result<(), ClientStartError> {
+        let socket_name = self
+            .socket_name
+            .as_deref()
+            .expect("WAYLAND_DISPLAY was not set yet by compositor");
+        let (command, args) = args.split_first().ok_or(ClientStartError::NoCommandGiven)?;
+        let mut child = Command::new(command)
+            .args(args)
+            .env("WAYLAND_DISPLAY", socket_name)
+            .spawn()?;
+        info!(pid = child.id(), command, "Spawned helper");
+        // reap it once it exits
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    /// Stop a client and start it again, or just start it if it isn't running
+    pub fn restart_client(&mut self, slot: ClientSlot) -> Result<(), String> {
+        let supervisor = self.supervisor_mut(slot);
+        if !supervisor.has_command() {
+            return Err("No client command was given".into());
+        }
+        if supervisor.is_running() {
+            info!(?slot, "Restart of client requested");
+            let pid = supervisor.pid();
+            supervisor
+                .request_restart()
+                .map_err(|err| format!("Couldn't stop the client: {}", err))?;
+            if let Some(pid) = pid {
+                self.kill_after_timeout(slot, pid);
+            }
+            return Ok(());
+        }
+        self.start_slot(slot)
+            .map_err(|err| format!("Couldn't start the client: {}", err))
+    }
+
+    /// Kill the client process `pid` if it didn't exit within [`KILL_TIMEOUT`] after SIGTERM
+    fn kill_after_timeout(&self, slot: ClientSlot, pid: u32) {
+        let res =
+            self.handle
+                .insert_source(Timer::from_duration(KILL_TIMEOUT), move |_, _, data| {
+                    match data.state.supervisor_mut(slot).kill(pid) {
+                        Ok(true) => warn!(?slot, pid, "Client didn't exit in time, killed it"),
+                        Ok(false) => {}
+                        Err(err) => warn!(?slot, pid, "Couldn't kill the client: {}", err),
+                    }
+                    TimeoutAction::Drop
+                });
+        if let Err(err) = res {
+            warn!(?slot, ?err, "Failed to schedule killing the client");
+        }
+    }
+
+    fn handle_client_event(&mut self, slot: ClientSlot, event: ClientEvent) {
+        match event {
+            ClientEvent::Restart(delay) => {
+                info!(?delay, ?slot, "restarting client");
+                self.handle
+                    .insert_source(Timer::from_duration(delay), move |_, _, data| {
+                        // it might have been started or stopped on request in the meantime
+                        let supervisor = data.state.supervisor_mut(slot);
+                        if supervisor.is_running() || supervisor.is_stopped() {
+                            return TimeoutAction::Drop;
+                        }
+                        match data.state.start_slot(slot) {
+                            Ok(()) => info!(?slot, "Client restarted successfully"),
+                            Err(e) => {
+                                error!(?slot, "Couldn't restart client: {}", e);
+                                let event = data.state.supervisor_mut(slot).exited(false);
+                                data.state.handle_client_event(slot, event);
+                            }
+                        }
+                        TimeoutAction::Drop
+                    })
+                    .expect("failed to schedule client restart");
+            }
+            ClientEvent::GiveUp(GiveUpAction::Exit) => {
+                info!(?slot, "client has finished. stopping...");
+                self.running.store(false, Ordering::SeqCst);
+            }
+            ClientEvent::GiveUp(GiveUpAction::Fallback) => {
+                warn!(?slot, "client has finished, showing fallback screen");
+            }
+            ClientEvent::Stopped => {
+                info!(?slot, "client stopped");
+            }
+        }
+    }
 }
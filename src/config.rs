@@ -0,0 +1,1267 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{NaiveTime, Weekday};
+use serde::{de, Deserialize, Deserializer};
+use smithay::{
+    input::keyboard::{xkb, Keysym, ModifiersState, XkbConfig},
+    reexports::calloop::{
+        signals::{Signal, Signals},
+        RegistrationToken,
+    },
+    utils::{Logical, Point, Transform},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    client::GiveUpAction,
+    drawing::{CLEAR_COLOR, CLEAR_COLOR_FULLSCREEN},
+    state::{Backend, BuedchenState},
+};
+
+/// Location of the configuration file if none is given on the command line
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/buedchen/config.toml";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Unable to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("Invalid configuration in {path}, key `{key}`: {source}")]
+    Parse {
+        path: PathBuf,
+        key: String,
+        source: toml::de::Error,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// File given on the command line, used again when reloading
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    pub colors: ColorConfig,
+    pub keyboard: KeyboardConfig,
+    pub output: OutputConfig,
+    /// Per-output rules, the first rule matching an output is used
+    pub outputs: Vec<OutputRule>,
+    /// Per-device libinput settings, all rules matching a device apply with later ones winning
+    pub inputs: Vec<InputRule>,
+    pub shortcuts: ShortcutConfig,
+    pub cursor: CursorConfig,
+    pub screenshot: ScreenshotConfig,
+    pub idle: IdleConfig,
+    pub schedule: ScheduleConfig,
+    pub lockdown: LockdownConfig,
+    /// Applications started in addition to the command line client, each pinned to an output
+    pub apps: Vec<AppConfig>,
+}
+
+impl Config {
+    /// Load the configuration from `path`, or from [`DEFAULT_CONFIG_PATH`] if it exists
+    ///
+    /// A missing default configuration file is not an error, the built-in defaults are used instead.
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let requested = path.map(Path::to_owned);
+        let path = match path {
+            Some(path) => path,
+            None => {
+                let path = Path::new(DEFAULT_CONFIG_PATH);
+                if !path.exists() {
+                    info!(
+                        "No configuration found at {}, using defaults",
+                        path.display()
+                    );
+                    return Ok(Config {
+                        path: requested,
+                        ..Config::default()
+                    });
+                }
+                path
+            }
+        };
+
+        let content = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        let mut config = Config::parse(&content).map_err(|(key, source)| ConfigError::Parse {
+            path: path.to_owned(),
+            key,
+            source,
+        })?;
+        config.path = requested;
+        info!("Loaded configuration from {}", path.display());
+        Ok(config)
+    }
+
+    /// Names of the sections that differ between two configurations
+    pub fn changed_sections(&self, other: &Config) -> Vec<&'static str> {
+        [
+            ("colors", self.colors != other.colors),
+            ("keyboard", self.keyboard != other.keyboard),
+            ("output", self.output != other.output),
+            ("outputs", self.outputs != other.outputs),
+            ("inputs", self.inputs != other.inputs),
+            ("shortcuts", self.shortcuts != other.shortcuts),
+            ("cursor", self.cursor != other.cursor),
+            ("screenshot", self.screenshot != other.screenshot),
+            ("idle", self.idle != other.idle),
+            ("schedule", self.schedule != other.schedule),
+            ("lockdown", self.lockdown != other.lockdown),
+            ("apps", self.apps != other.apps),
+        ]
+        .into_iter()
+        .filter_map(|(section, changed)| changed.then_some(section))
+        .collect()
+    }
+
+    /// Settings for an output, taken from the first matching rule or the `[output]` defaults
+    pub fn output_settings(&self, identity: &OutputIdentity) -> OutputSettings {
+        let rule = self.outputs.iter().find(|rule| rule.matches(identity));
+        OutputSettings {
+            enabled: rule.map_or(true, |rule| rule.enabled),
+            mode: rule.and_then(|rule| rule.mode).unwrap_or(self.output.mode),
+            transform: rule
+                .and_then(|rule| rule.transform)
+                .unwrap_or(self.output.transform),
+            scale: rule.and_then(|rule| rule.scale),
+            position: rule
+                .and_then(|rule| rule.position)
+                .map(|[x, y]| (x, y).into()),
+            mirror: rule.and_then(|rule| rule.mirror.clone()),
+            mirror_scaling: rule.map(|rule| rule.mirror_scaling).unwrap_or_default(),
+        }
+    }
+
+    /// Colour adjustment of an output, taken from the first matching rule
+    pub fn color_adjustment(&self, identity: &OutputIdentity) -> ColorAdjustment {
+        let rule = self.outputs.iter().find(|rule| rule.matches(identity));
+        let neutral = ColorAdjustment::default();
+        ColorAdjustment {
+            gamma: rule.and_then(|rule| rule.gamma).unwrap_or(neutral.gamma),
+            brightness: rule
+                .and_then(|rule| rule.brightness)
+                .unwrap_or(neutral.brightness),
+            temperature: rule
+                .and_then(|rule| rule.temperature)
+                .unwrap_or(neutral.temperature),
+        }
+    }
+
+    /// Parse a configuration, on error returns the path of the offending key alongside the error
+    pub fn parse(content: &str) -> Result<Config, (String, toml::de::Error)> {
+        serde_path_to_error::deserialize(toml::Deserializer::new(content))
+            .map_err(|err| (err.path().to_string(), err.into_inner()))
+    }
+}
+
+/// A RGBA colour, written as `#rrggbb` or `#rrggbbaa` in the configuration
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(pub [f32; 4]);
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| (hex.len() == 6 || hex.len() == 8) && hex.is_ascii())
+            .ok_or_else(|| format!("expected a colour like #rrggbb or #rrggbbaa, got {}", s))?;
+
+        let mut color = [1.0; 4];
+        for (index, channel) in color.iter_mut().enumerate().take(hex.len() / 2) {
+            let value = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)
+                .map_err(|_| format!("invalid hex digits in colour {}", s))?;
+            *channel = value as f32 / 255.0;
+        }
+        Ok(Color(color))
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorConfig {
+    /// Colour drawn behind regular windows
+    pub background: Color,
+    /// Colour drawn behind a fullscreen window
+    pub fullscreen: Color,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        ColorConfig {
+            background: Color(CLEAR_COLOR),
+            fullscreen: Color(CLEAR_COLOR_FULLSCREEN),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyboardConfig {
+    pub rules: String,
+    pub model: String,
+    pub layout: String,
+    pub variant: String,
+    pub options: Option<String>,
+    /// Delay in milliseconds before a held key starts repeating
+    pub repeat_delay: u16,
+    /// Key repeats per second, 0 disables repeating
+    pub repeat_rate: u16,
+    /// Further layouts to switch to at runtime, after the main one
+    pub layouts: Vec<KeyboardLayout>,
+}
+
+/// A layout besides the main one of [`KeyboardConfig`], sharing its rules and model
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyboardLayout {
+    pub layout: String,
+    #[serde(default)]
+    pub variant: String,
+    /// Defaults to the options of the main layout
+    pub options: Option<String>,
+}
+
+impl Default for KeyboardConfig {
+    fn default() -> Self {
+        KeyboardConfig {
+            rules: String::new(),
+            model: String::new(),
+            layout: String::new(),
+            variant: String::new(),
+            options: None,
+            repeat_delay: 200,
+            repeat_rate: 25,
+            layouts: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardConfig {
+    /// Number of layouts to switch between, including the main one
+    pub fn layout_count(&self) -> usize {
+        1 + self.layouts.len()
+    }
+
+    /// xkb configuration of the layout at `index`, 0 is the main one
+    pub fn xkb_config(&self, index: usize) -> XkbConfig<'_> {
+        let (layout, variant, options) = match index.checked_sub(1) {
+            Some(index) if index < self.layouts.len() => {
+                let layout = &self.layouts[index];
+                (
+                    &layout.layout,
+                    &layout.variant,
+                    layout.options.as_ref().or(self.options.as_ref()),
+                )
+            }
+            _ => (&self.layout, &self.variant, self.options.as_ref()),
+        };
+        XkbConfig {
+            rules: &self.rules,
+            model: &self.model,
+            layout,
+            variant,
+            options: options.cloned(),
+        }
+    }
+
+    /// Name of the layout at `index` like `de` or `ch(fr)`, `default` for an empty main layout
+    pub fn layout_name(&self, index: usize) -> String {
+        let config = self.xkb_config(index);
+        match (config.layout, config.variant) {
+            ("", _) => "default".into(),
+            (layout, "") => layout.into(),
+            (layout, variant) => format!("{}({})", layout, variant),
+        }
+    }
+
+    /// Index of the layout with the given [`KeyboardConfig::layout_name`]
+    pub fn find_layout(&self, name: &str) -> Option<usize> {
+        (0..self.layout_count()).find(|index| self.layout_name(*index) == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub mode: ModeSelection,
+    /// One of `normal`, `90`, `180`, `270`, `flipped`, `flipped-90`, `flipped-180` or `flipped-270`
+    #[serde(deserialize_with = "deserialize_transform")]
+    pub transform: Transform,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            mode: ModeSelection::default(),
+            transform: Transform::Normal,
+        }
+    }
+}
+
+/// Connector name and EDID information used to match an output against [`OutputRule`]s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputIdentity {
+    pub connector: String,
+    pub make: String,
+    pub model: String,
+    pub serial: Option<String>,
+}
+
+/// Settings for the outputs matching all of the given connector, make, model and serial
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputRule {
+    /// Connector name like `HDMI-A-1`
+    pub connector: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub mode: Option<ModeSelection>,
+    #[serde(default, deserialize_with = "deserialize_optional_transform")]
+    pub transform: Option<Transform>,
+    /// Fractional scale, e.g. `1.5`
+    #[serde(default, deserialize_with = "deserialize_scale")]
+    pub scale: Option<f64>,
+    /// Position of the top left corner in the global space, outputs without one are placed
+    /// left-to-right next to the positioned ones
+    pub position: Option<[i32; 2]>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// Connector name of another output to show the same content as, instead of extending the
+    /// global space
+    pub mirror: Option<String>,
+    #[serde(default)]
+    pub mirror_scaling: MirrorScaling,
+    /// Exponent of the gamma curve, `1.0` leaves the colours as they are
+    #[serde(default, deserialize_with = "deserialize_gamma")]
+    pub gamma: Option<f32>,
+    /// Multiplier of all colours between 0 and 1, for panels without a brightness control
+    #[serde(default, deserialize_with = "deserialize_brightness")]
+    pub brightness: Option<f32>,
+    /// Colour temperature of white in Kelvin, lower values are warmer
+    #[serde(default, deserialize_with = "deserialize_temperature")]
+    pub temperature: Option<u32>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl OutputRule {
+    pub fn matches(&self, identity: &OutputIdentity) -> bool {
+        self.connector
+            .as_ref()
+            .map_or(true, |connector| *connector == identity.connector)
+            && self
+                .make
+                .as_ref()
+                .map_or(true, |make| *make == identity.make)
+            && self
+                .model
+                .as_ref()
+                .map_or(true, |model| *model == identity.model)
+            && self
+                .serial
+                .as_ref()
+                .map_or(true, |serial| Some(serial) == identity.serial.as_ref())
+    }
+}
+
+/// How a mirrored output is fit onto an output with a different resolution
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MirrorScaling {
+    /// Keep the aspect ratio and fill the remaining area with the background colour
+    #[default]
+    Letterbox,
+    /// Stretch the content over the whole output
+    Stretch,
+}
+
+/// Effective settings of a single output
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputSettings {
+    pub enabled: bool,
+    pub mode: ModeSelection,
+    pub transform: Transform,
+    pub scale: Option<f64>,
+    pub position: Option<Point<i32, Logical>>,
+    pub mirror: Option<String>,
+    pub mirror_scaling: MirrorScaling,
+}
+
+/// Colour adjustment of an output, applied through its gamma ramps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorAdjustment {
+    pub gamma: f32,
+    pub brightness: f32,
+    pub temperature: u32,
+}
+
+impl Default for ColorAdjustment {
+    fn default() -> Self {
+        ColorAdjustment {
+            gamma: 1.0,
+            brightness: 1.0,
+            temperature: NEUTRAL_TEMPERATURE,
+        }
+    }
+}
+
+impl ColorAdjustment {
+    pub fn validate(&self) -> Result<(), String> {
+        check_gamma(self.gamma)?;
+        check_brightness(self.brightness)?;
+        check_temperature(self.temperature)?;
+        Ok(())
+    }
+}
+
+/// Colour temperature leaving white as it is
+pub const NEUTRAL_TEMPERATURE: u32 = 6500;
+
+fn check_gamma(gamma: f32) -> Result<f32, String> {
+    if !gamma.is_finite() || gamma <= 0.0 {
+        return Err(format!("gamma has to be a positive number, got {}", gamma));
+    }
+    Ok(gamma)
+}
+
+fn check_brightness(brightness: f32) -> Result<f32, String> {
+    if !(0.0..=1.0).contains(&brightness) {
+        return Err(format!(
+            "brightness has to be between 0 and 1, got {}",
+            brightness
+        ));
+    }
+    Ok(brightness)
+}
+
+fn check_temperature(temperature: u32) -> Result<u32, String> {
+    if !(1000..=10000).contains(&temperature) {
+        return Err(format!(
+            "temperature has to be between 1000 and 10000 Kelvin, got {}",
+            temperature
+        ));
+    }
+    Ok(temperature)
+}
+
+fn deserialize_gamma<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f32>, D::Error> {
+    check_gamma(f32::deserialize(deserializer)?)
+        .map(Some)
+        .map_err(de::Error::custom)
+}
+
+fn deserialize_brightness<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<f32>, D::Error> {
+    check_brightness(f32::deserialize(deserializer)?)
+        .map(Some)
+        .map_err(de::Error::custom)
+}
+
+fn deserialize_temperature<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u32>, D::Error> {
+    check_temperature(u32::deserialize(deserializer)?)
+        .map(Some)
+        .map_err(de::Error::custom)
+}
+
+fn deserialize_accel_speed<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<f64>, D::Error> {
+    let speed = f64::deserialize(deserializer)?;
+    if !(-1.0..=1.0).contains(&speed) {
+        return Err(de::Error::custom(format!(
+            "accel_speed has to be between -1 and 1, got {}",
+            speed
+        )));
+    }
+    Ok(Some(speed))
+}
+
+fn deserialize_scale<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    let scale = f64::deserialize(deserializer)?;
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(de::Error::custom(format!(
+            "scale has to be a positive number, got {}",
+            scale
+        )));
+    }
+    Ok(Some(scale))
+}
+
+fn deserialize_optional_transform<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Transform>, D::Error> {
+    deserialize_transform(deserializer).map(Some)
+}
+
+fn deserialize_transform<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Transform, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_transform(&value).map_err(de::Error::custom)
+}
+
+pub fn parse_transform(s: &str) -> Result<Transform, String> {
+    Ok(match s {
+        "normal" => Transform::Normal,
+        "90" => Transform::_90,
+        "180" => Transform::_180,
+        "270" => Transform::_270,
+        "flipped" => Transform::Flipped,
+        "flipped-90" => Transform::Flipped90,
+        "flipped-180" => Transform::Flipped180,
+        "flipped-270" => Transform::Flipped270,
+        _ => {
+            return Err(format!(
+                "expected normal, 90, 180, 270, flipped, flipped-90, flipped-180 or flipped-270, got {}",
+                s
+            ))
+        }
+    })
+}
+
+/// Inverse of [`parse_transform`]
+pub fn transform_name(transform: Transform) -> &'static str {
+    match transform {
+        Transform::Normal => "normal",
+        Transform::_90 => "90",
+        Transform::_180 => "180",
+        Transform::_270 => "270",
+        Transform::Flipped => "flipped",
+        Transform::Flipped90 => "flipped-90",
+        Transform::Flipped180 => "flipped-180",
+        Transform::Flipped270 => "flipped-270",
+    }
+}
+
+/// How the mode of a newly connected output is chosen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ModeSelection {
+    /// The mode the display marks as preferred
+    #[default]
+    Preferred,
+    /// The mode with the most pixels, highest refresh rate first
+    HighestResolution,
+    /// The mode with the highest refresh rate, most pixels first
+    HighestRefresh,
+    /// A fixed size and optionally refresh rate in mHz, falls back to the preferred mode
+    Exact {
+        width: u16,
+        height: u16,
+        refresh: Option<u32>,
+    },
+}
+
+impl FromStr for ModeSelection {
+    type Err = String;
+
+    /// Parses `preferred`, `highest-resolution`, `highest-refresh` or `WIDTHxHEIGHT[@REFRESH]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preferred" => return Ok(ModeSelection::Preferred),
+            "highest-resolution" => return Ok(ModeSelection::HighestResolution),
+            "highest-refresh" => return Ok(ModeSelection::HighestRefresh),
+            _ => {}
+        }
+
+        let invalid = || {
+            format!(
+                "expected preferred, highest-resolution, highest-refresh or WIDTHxHEIGHT[@REFRESH], got {}",
+                s
+            )
+        };
+        let (size, refresh) = match s.split_once('@') {
+            Some((size, refresh)) => {
+                let refresh = refresh.parse::<f64>().map_err(|_| invalid())?;
+                if refresh <= 0.0 {
+                    return Err(invalid());
+                }
+                (size, Some((refresh * 1000.0).round() as u32))
+            }
+            None => (s, None),
+        };
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        Ok(ModeSelection::Exact {
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+            refresh,
+        })
+    }
+}
+
+impl TryFrom<String> for ModeSelection {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// libinput settings for the devices matching all of the given name, id and type
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputRule {
+    /// Device name as reported by libinput, e.g. `ELAN Touchscreen`
+    pub name: Option<String>,
+    /// USB vendor and product id in hex, e.g. `046d:c52b`
+    pub id: Option<DeviceId>,
+    #[serde(rename = "type")]
+    pub device_type: Option<DeviceType>,
+    /// Tap-to-click
+    pub tap: Option<bool>,
+    /// Tap-and-drag
+    pub drag: Option<bool>,
+    pub accel_profile: Option<AccelProfile>,
+    /// Pointer acceleration between -1 and 1
+    #[serde(default, deserialize_with = "deserialize_accel_speed")]
+    pub accel_speed: Option<f64>,
+    pub natural_scroll: Option<bool>,
+    pub scroll_method: Option<ScrollMethod>,
+    pub left_handed: Option<bool>,
+    /// Disable the touchpad while typing
+    pub dwt: Option<bool>,
+    pub send_events: Option<SendEvents>,
+    /// First two rows of the 3x3 matrix applied to absolute coordinates of touchscreens
+    pub calibration_matrix: Option<[f32; 6]>,
+    /// Connector name of the output touchscreens and tablets are mapped onto, instead of
+    /// finding it by EDID or physical size
+    pub output: Option<String>,
+}
+
+impl InputRule {
+    /// `has_type` tells whether the device is of the given type, a device can have several
+    pub fn matches(&self, name: &str, id: DeviceId, has_type: impl Fn(DeviceType) -> bool) -> bool {
+        self.name.as_ref().map_or(true, |n| n == name)
+            && self.id.map_or(true, |i| i == id)
+            && self.device_type.map_or(true, has_type)
+    }
+}
+
+/// USB vendor and product id of an input device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct DeviceId {
+    pub vendor: u32,
+    pub product: u32,
+}
+
+impl FromStr for DeviceId {
+    type Err = String;
+
+    /// Parses `VENDOR:PRODUCT` in hex
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected VENDOR:PRODUCT in hex, got {}", s);
+        let (vendor, product) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(DeviceId {
+            vendor: u32::from_str_radix(vendor, 16).map_err(|_| invalid())?,
+            product: u32::from_str_radix(product, 16).map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl TryFrom<String> for DeviceId {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeviceType {
+    Keyboard,
+    Pointer,
+    Touchpad,
+    Touch,
+    Tablet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccelProfile {
+    Flat,
+    Adaptive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScrollMethod {
+    None,
+    TwoFinger,
+    Edge,
+    OnButtonDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SendEvents {
+    Enabled,
+    Disabled,
+    /// For touchpads, disabled while an external mouse is connected
+    DisabledOnExternalMouse,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShortcutConfig {
+    /// Key combinations that stop the compositor
+    pub quit: Vec<KeyCombo>,
+    /// Key combinations that write screenshots of all outputs
+    pub screenshot: Vec<KeyCombo>,
+    /// Further key combinations and their actions, checked after `quit` and `screenshot`
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for ShortcutConfig {
+    fn default() -> Self {
+        ShortcutConfig {
+            quit: vec![
+                "Ctrl+Alt+BackSpace".parse().unwrap(),
+                "Logo+q".parse().unwrap(),
+            ],
+            screenshot: Vec::new(),
+            bindings: Vec::new(),
+        }
+    }
+}
+
+/// A key combination and the action it triggers
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "BindingConfig")]
+pub struct Binding {
+    pub keys: KeyCombo,
+    pub action: Action,
+}
+
+/// What a [`Binding`] does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    /// Restart the command line client, or the application on `output`
+    RestartClient {
+        output: Option<String>,
+    },
+    Screenshot,
+    /// Turn `output`, or all outputs, off or on again
+    ToggleOutputPower {
+        output: Option<String>,
+    },
+    /// Give the keyboard focus to the next window
+    CycleFocus,
+    /// Start a helper command, it isn't restarted when it exits
+    Spawn {
+        command: Vec<String>,
+    },
+    /// Show all windows side by side in front of the one in the foreground
+    ToggleWindowPreview,
+    /// Switch to the next of the configured keyboard layouts
+    NextKeyboardLayout,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BindingConfig {
+    keys: KeyCombo,
+    action: ActionName,
+    #[serde(default)]
+    command: Vec<String>,
+    output: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ActionName {
+    Quit,
+    RestartClient,
+    Screenshot,
+    ToggleOutputPower,
+    CycleFocus,
+    Spawn,
+    ToggleWindowPreview,
+    NextKeyboardLayout,
+}
+
+impl TryFrom<BindingConfig> for Binding {
+    type Error = String;
+
+    fn try_from(config: BindingConfig) -> Result<Self, Self::Error> {
+        let takes_output = matches!(
+            config.action,
+            ActionName::RestartClient | ActionName::ToggleOutputPower
+        );
+        if config.output.is_some() && !takes_output {
+            return Err(format!("{:?} doesn't take an output", config.action));
+        }
+        if !config.command.is_empty() && !matches!(config.action, ActionName::Spawn) {
+            return Err(format!("{:?} doesn't take a command", config.action));
+        }
+
+        let action = match config.action {
+            ActionName::Quit => Action::Quit,
+            ActionName::RestartClient => Action::RestartClient {
+                output: config.output,
+            },
+            ActionName::Screenshot => Action::Screenshot,
+            ActionName::ToggleOutputPower => Action::ToggleOutputPower {
+                output: config.output,
+            },
+            ActionName::CycleFocus => Action::CycleFocus,
+            ActionName::Spawn if config.command.is_empty() => {
+                return Err("spawn needs a command".into())
+            }
+            ActionName::Spawn => Action::Spawn {
+                command: config.command,
+            },
+            ActionName::ToggleWindowPreview => Action::ToggleWindowPreview,
+            ActionName::NextKeyboardLayout => Action::NextKeyboardLayout,
+        };
+        Ok(Binding {
+            keys: config.keys,
+            action,
+        })
+    }
+}
+
+/// A key together with the modifiers that have to be held, e.g. `Ctrl+Alt+BackSpace`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeyCombo {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub logo: bool,
+    pub key: Key,
+}
+
+/// Key of a [`KeyCombo`], given by its keysym name or as `code:NUMBER`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Sym(Keysym),
+    /// Keycode as sent to clients, independent of the keymap, e.g. as shown by `wev`
+    Code(u32),
+}
+
+impl KeyCombo {
+    pub fn matches(&self, modifiers: &ModifiersState, keysym: Keysym, keycode: u32) -> bool {
+        self.ctrl == modifiers.ctrl
+            && self.alt == modifiers.alt
+            && self.shift == modifiers.shift
+            && self.logo == modifiers.logo
+            && match self.key {
+                Key::Sym(sym) => sym == keysym,
+                Key::Code(code) => code == keycode,
+            }
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut combo = KeyCombo {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+            key: Key::Code(0),
+        };
+
+        let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts.pop().filter(|key| !key.is_empty());
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => combo.ctrl = true,
+                "alt" => combo.alt = true,
+                "shift" => combo.shift = true,
+                "logo" | "super" | "mod4" => combo.logo = true,
+                _ => return Err(format!("unknown modifier {} in {}", modifier, s)),
+            }
+        }
+
+        let key = key.ok_or_else(|| format!("missing key in {}", s))?;
+        if let Some(code) = key.strip_prefix("code:") {
+            let code = code
+                .parse()
+                .map_err(|_| format!("invalid keycode {} in {}", code, s))?;
+            combo.key = Key::Code(code);
+            return Ok(combo);
+        }
+        let mut keysym = xkb::keysym_from_name(key, xkb::KEYSYM_NO_FLAGS);
+        if keysym == Keysym::NoSymbol {
+            keysym = xkb::keysym_from_name(key, xkb::KEYSYM_CASE_INSENSITIVE);
+        }
+        if keysym == Keysym::NoSymbol {
+            return Err(format!("unknown key {} in {}", key, s));
+        }
+        combo.key = Key::Sym(keysym);
+        Ok(combo)
+    }
+}
+
+impl TryFrom<String> for KeyCombo {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+            (self.logo, "Logo+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        match self.key {
+            Key::Sym(keysym) => f.write_str(&xkb::keysym_get_name(keysym)),
+            Key::Code(code) => write!(f, "code:{}", code),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CursorConfig {
+    /// Xcursor theme, defaults to `$XCURSOR_THEME` or `default`
+    pub theme: Option<String>,
+    /// Nominal cursor size, defaults to `$XCURSOR_SIZE` or 24
+    pub size: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScreenshotConfig {
+    /// Where screenshots triggered by a shortcut or `SIGUSR1` are written
+    pub directory: PathBuf,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        ScreenshotConfig {
+            directory: PathBuf::from("/tmp"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdleConfig {
+    /// Seconds without input until the outputs are dimmed
+    pub dim_after: Option<u64>,
+    /// Seconds without input until the outputs are turned off
+    pub blank_after: Option<u64>,
+    /// Opacity of the black overlay darkening dimmed outputs, between 0 and 1
+    pub dim_level: f32,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        IdleConfig {
+            dim_after: None,
+            blank_after: None,
+            dim_level: 0.6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// Stop the clients while the outputs are off and start them again afterwards
+    pub stop_client: bool,
+    /// Times the outputs are on, without any period they are always on
+    pub periods: Vec<SchedulePeriod>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchedulePeriod {
+    /// Days the period starts on
+    pub days: Vec<Day>,
+    /// Local time the outputs are turned on
+    pub on: TimeOfDay,
+    /// Local time the outputs are turned off, the next day if it isn't after `on`
+    pub off: TimeOfDay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Day {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Day {
+    pub fn weekday(self) -> Weekday {
+        match self {
+            Day::Mon => Weekday::Mon,
+            Day::Tue => Weekday::Tue,
+            Day::Wed => Weekday::Wed,
+            Day::Thu => Weekday::Thu,
+            Day::Fri => Weekday::Fri,
+            Day::Sat => Weekday::Sat,
+            Day::Sun => Weekday::Sun,
+        }
+    }
+}
+
+/// A wall-clock time written as `HH:MM`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl FromStr for TimeOfDay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NaiveTime::parse_from_str(s, "%H:%M")
+            .map(TimeOfDay)
+            .map_err(|_| format!("expected a time like 07:30, got {}", s))
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockdownConfig {
+    /// Ignore the compositor shortcuts, like quitting or switching the VT, until unlocked
+    pub enabled: bool,
+    /// Seconds the shortcuts stay available after unlocking
+    pub unlock_duration: u64,
+    /// Key combination that unlocks when held for `chord_hold` seconds
+    pub chord: Option<KeyCombo>,
+    pub chord_hold: u64,
+    /// Text that unlocks when typed within `window` seconds
+    pub sequence: Option<String>,
+    /// Output corners that unlock when touched in this order within `window` seconds
+    pub corners: Vec<Corner>,
+    /// Size of the corner areas in logical pixels
+    pub corner_size: u32,
+    pub window: u64,
+}
+
+impl Default for LockdownConfig {
+    fn default() -> Self {
+        LockdownConfig {
+            enabled: false,
+            unlock_duration: 120,
+            chord: None,
+            chord_hold: 3,
+            sequence: None,
+            corners: Vec::new(),
+            corner_size: 100,
+            window: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// An application whose toplevels are shown on a single output
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig {
+    /// Connector name like `HDMI-A-1`
+    pub output: String,
+    /// Executable and its arguments
+    pub command: Vec<String>,
+    /// Also pin toplevels with this app id, for applications opening their windows from another
+    /// process than the started one
+    pub app_id: Option<String>,
+    /// What to do once the application is not restarted anymore, unlike the command line client
+    /// it keeps the compositor running by default
+    #[serde(default = "fallback")]
+    pub on_give_up: GiveUpAction,
+}
+
+fn fallback() -> GiveUpAction {
+    GiveUpAction::Fallback
+}
+
+/// Backend specific reload, expected to call [`BuedchenState::reload_config`] and apply the
+/// remaining settings itself
+pub type ReloadFn<BackendData> =
+    fn(&mut BuedchenState<BackendData>) -> Result<ReloadReport, String>;
+
+/// Sections that differ from the previous configuration, and those of them not applied
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    pub changed: Vec<&'static str>,
+    pub not_applied: Vec<&'static str>,
+}
+
+impl ReloadReport {
+    /// Log the changed `sections` that can't be applied without a restart and remember them
+    pub fn not_applied(&mut self, sections: &[&str]) {
+        for section in self
+            .changed
+            .iter()
+            .copied()
+            .filter(|section| sections.contains(section))
+        {
+            warn!(
+                section,
+                "Setting changed but is not applied until the compositor is restarted"
+            );
+            if !self.not_applied.contains(&section) {
+                self.not_applied.push(section);
+            }
+        }
+    }
+}
+
+/// Reload the configuration every time the compositor receives SIGHUP or a reload request
+pub fn reload_on_sighup<BackendData: Backend + 'static>(
+    state: &mut BuedchenState<BackendData>,
+    reload: ReloadFn<BackendData>,
+) -> Result<RegistrationToken, Box<dyn std::error::Error>> {
+    state.reload = Some(reload);
+    let signals = Signals::new(&[Signal::SIGHUP])?;
+    let token = state
+        .handle
+        .insert_source(signals, |_, _, data| {
+            info!("Received SIGHUP, reloading configuration");
+            // failures are logged by the reload itself
+            let _ = data.state.reload();
+        })
+        .map_err(|err| err.error)?;
+    Ok(token)
+}
+
+impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
+    /// Reload the configuration with the reload of the running backend
+    pub fn reload(&mut self) -> Result<ReloadReport, String> {
+        match self.reload {
+            Some(reload) => reload(self),
+            None => Err("The backend doesn't support reloading the configuration".into()),
+        }
+    }
+
+    /// Read the configuration file again and apply the settings every backend supports
+    ///
+    /// Returns the previous configuration so the backend can compare the remaining sections,
+    /// or an error if the new configuration could not be loaded and the current one is kept.
+    pub fn reload_config(&mut self) -> Result<(Config, ReloadReport), String> {
+        let config = match Config::load(self.config.path.as_deref()) {
+            Ok(config) => config,
+            Err(err) => {
+                error!("Keeping the current configuration: {}", err);
+                return Err(err.to_string());
+            }
+        };
+        let previous = std::mem::replace(&mut self.config, config);
+        let mut report = ReloadReport {
+            changed: previous.changed_sections(&self.config),
+            not_applied: Vec::new(),
+        };
+        info!(changed = ?report.changed, "Configuration reloaded");
+
+        if previous.keyboard != self.config.keyboard {
+            let keyboard_config = self.config.keyboard.clone();
+            let keyboard = self.seat.get_keyboard().unwrap();
+            match keyboard.set_xkb_config(self, keyboard_config.xkb_config(0)) {
+                Ok(()) => {
+                    // the layouts might have changed, start over with the main one
+                    self.keyboard_layout = 0;
+                    keyboard.change_repeat_info(
+                        keyboard_config.repeat_rate.into(),
+                        keyboard_config.repeat_delay.into(),
+                    );
+                }
+                Err(err) => {
+                    // the previous keymap stays in use, so does its section
+                    error!(
+                        "Failed to apply keymap, keeping the keyboard settings: {:?}",
+                        err
+                    );
+                    self.config.keyboard = previous.keyboard.clone();
+                    report.not_applied.push("keyboard");
+                }
+            }
+        }
+
+        if previous.colors != self.config.colors {
+            for output in self.space.outputs().cloned().collect::<Vec<_>>() {
+                self.backend_data.reset_buffers(&output);
+            }
+        }
+
+        // the applications keep running as they were started
+        report.not_applied(&["apps"]);
+
+        Ok((previous, report))
+    }
+
+    /// Switch to the configured keyboard layout at `index`, the clients get the new keymap
+    pub fn set_keyboard_layout(&mut self, index: usize) -> Result<(), String> {
+        let keyboard_config = self.config.keyboard.clone();
+        if index >= keyboard_config.layout_count() {
+            return Err(format!("No keyboard layout at index {}", index));
+        }
+        let keyboard = self.seat.get_keyboard().unwrap();
+        keyboard
+            .set_xkb_config(self, keyboard_config.xkb_config(index))
+            .map_err(|err| format!("Failed to apply keymap: {:?}", err))?;
+        self.keyboard_layout = index;
+        info!(
+            layout = keyboard_config.layout_name(index),
+            "Keyboard layout changed"
+        );
+        Ok(())
+    }
+
+    /// Switch to the next configured keyboard layout, after the last one comes the main one
+    pub fn next_keyboard_layout(&mut self) {
+        let index = (self.keyboard_layout + 1) % self.config.keyboard.layout_count();
+        if let Err(err) = self.set_keyboard_layout(index) {
+            error!("{}", err);
+        }
+    }
+}
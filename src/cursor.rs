@@ -1,6 +1,8 @@
 use std::{io::Read, time::Duration};
 
 use tracing::warn;
+
+use crate::config::CursorConfig;
 use xcursor::{
     parser::{parse_xcursor, Image},
     CursorTheme,
@@ -14,13 +16,19 @@ pub struct Cursor {
 }
 
 impl Cursor {
-    pub fn load() -> Cursor {
-        let name = std::env::var("XCURSOR_THEME")
-            .ok()
+    pub fn load(config: &CursorConfig) -> Cursor {
+        let name = config
+            .theme
+            .clone()
+            .or_else(|| std::env::var("XCURSOR_THEME").ok())
             .unwrap_or_else(|| "default".into());
-        let size = std::env::var("XCURSOR_SIZE")
-            .ok()
-            .and_then(|s| s.parse().ok())
+        let size = config
+            .size
+            .or_else(|| {
+                std::env::var("XCURSOR_SIZE")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+            })
             .unwrap_or(24);
 
         let theme = CursorTheme::load(&name);
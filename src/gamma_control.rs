@@ -0,0 +1,294 @@
+//! Server side of the `wlr-gamma-control-unstable-v1` protocol
+//!
+//! Only one client at a time controls the gamma ramps of an output. Once its control is
+//! destroyed the backend goes back to the ramps of the configured [`ColorAdjustment`], which are
+//! computed by [`color_ramps`].
+
+use std::{fs::File, io::Read};
+
+use rustix::fs::{fcntl_getfl, fcntl_setfl, OFlags};
+
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::gamma_control::v1::server::{
+            zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+            zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+};
+use tracing::{trace, warn};
+
+use crate::config::{ColorAdjustment, NEUTRAL_TEMPERATURE};
+
+const VERSION: u32 = 1;
+
+pub trait GammaControlHandler {
+    fn gamma_control_state(&mut self) -> &mut GammaControlManagerState;
+
+    /// Number of entries per channel of the gamma ramps of `output`, `None` if it has none
+    fn gamma_size(&mut self, output: &Output) -> Option<u32>;
+
+    /// Replace the gamma ramps of `output` with the red, green and blue ramps following each
+    /// other, or go back to the configured ones with `None`
+    ///
+    /// Returns `false` if the ramps could not be set.
+    fn set_gamma(&mut self, output: &Output, ramps: Option<Vec<u16>>) -> bool;
+}
+
+pub struct GammaControlManagerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+#[derive(Debug)]
+pub struct GammaControlManagerState {
+    global: GlobalId,
+    /// Controls of outputs that are still valid, at most one per output
+    controls: Vec<ZwlrGammaControlV1>,
+}
+
+impl GammaControlManagerState {
+    /// Create the `zwlr_gamma_control_manager_v1` global, only visible to clients passing `filter`
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData>
+            + Dispatch<ZwlrGammaControlManagerV1, ()>
+            + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        let global = display.create_global::<D, ZwlrGammaControlManagerV1, _>(
+            VERSION,
+            GammaControlManagerGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+
+        GammaControlManagerState {
+            global,
+            controls: Vec::new(),
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Invalidate the control of an output that disappeared
+    pub fn output_removed(&mut self, output: &Output) {
+        self.controls.retain(|control| {
+            let removed = controls(control, output);
+            if removed {
+                control.failed();
+            }
+            !removed
+        });
+    }
+}
+
+fn controls(control: &ZwlrGammaControlV1, output: &Output) -> bool {
+    control.data::<Option<Output>>() == Some(&Some(output.clone()))
+}
+
+/// Red, green and blue ramps with `size` entries each for a colour adjustment
+pub fn color_ramps(adjustment: &ColorAdjustment, size: usize) -> Vec<u16> {
+    let white = white_point(adjustment.temperature);
+    let neutral = white_point(NEUTRAL_TEMPERATURE);
+    let max = size.saturating_sub(1).max(1) as f32;
+    (0..3)
+        .flat_map(|channel| {
+            let factor = adjustment.brightness * white[channel] / neutral[channel];
+            (0..size).map(move |i| {
+                let value = (i as f32 / max * factor).clamp(0.0, 1.0);
+                (value.powf(1.0 / adjustment.gamma) * u16::MAX as f32).round() as u16
+            })
+        })
+        .collect()
+}
+
+/// Colour of a black body at `temperature` Kelvin, approximated as described by Tanner Helland
+fn white_point(temperature: u32) -> [f32; 3] {
+    let t = temperature as f32 / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (t - 60.0).powf(-0.13320476)
+    };
+    let green = if t <= 66.0 {
+        99.4708 * t.ln() - 161.11957
+    } else {
+        288.12216 * (t - 60.0).powf(-0.075514846)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (t - 10.0).ln() - 305.0448
+    };
+    [red, green, blue].map(|c| c.clamp(0.0, 255.0) / 255.0)
+}
+
+impl<D> GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData, D>
+    for GammaControlManagerState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrGammaControlManagerV1>,
+        _global_data: &GammaControlManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GammaControlManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlManagerState
+where
+    D: Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, Option<Output>>
+        + GammaControlHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrGammaControlManagerV1,
+        request: zwlr_gamma_control_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_manager_v1::Request::GetGammaControl { id, output } => {
+                let output = Output::from_resource(&output);
+                trace!(output = ?output.as_ref().map(Output::name), "New gamma control");
+                let control = data_init.init(id, output.clone());
+                let size = output.as_ref().and_then(|output| {
+                    let taken = state
+                        .gamma_control_state()
+                        .controls
+                        .iter()
+                        .any(|control| controls(control, output));
+                    if taken {
+                        None
+                    } else {
+                        state.gamma_size(output)
+                    }
+                });
+                match size {
+                    Some(size) => {
+                        control.gamma_size(size);
+                        state.gamma_control_state().controls.push(control);
+                    }
+                    None => control.failed(),
+                }
+            }
+            zwlr_gamma_control_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlV1, Option<Output>, D> for GammaControlManagerState
+where
+    D: Dispatch<ZwlrGammaControlV1, Option<Output>> + GammaControlHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrGammaControlV1,
+        request: zwlr_gamma_control_v1::Request,
+        data: &Option<Output>,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_v1::Request::SetGamma { fd } => {
+                // failed was already sent for controls that are not valid
+                let Some(output) = data else {
+                    return;
+                };
+                if !state.gamma_control_state().controls.contains(resource) {
+                    return;
+                }
+                let Some(size) = state.gamma_size(output) else {
+                    return;
+                };
+
+                // the ramps have to be there already, a client must not be able to stall the
+                // event loop with a pipe it never writes to
+                let mut bytes = vec![0u8; size as usize * 3 * 2];
+                let read = fcntl_getfl(&fd)
+                    .and_then(|flags| fcntl_setfl(&fd, flags | OFlags::NONBLOCK))
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| File::from(fd).read_exact(&mut bytes));
+                let ramps = match read {
+                    Ok(()) => Some(
+                        bytes
+                            .chunks_exact(2)
+                            .map(|value| u16::from_ne_bytes([value[0], value[1]]))
+                            .collect(),
+                    ),
+                    Err(err) => {
+                        warn!("Failed to read gamma ramps: {}", err);
+                        None
+                    }
+                };
+                if !ramps.is_some_and(|ramps| state.set_gamma(output, Some(ramps))) {
+                    resource.failed();
+                    state
+                        .gamma_control_state()
+                        .controls
+                        .retain(|control| control != resource);
+                }
+            }
+            zwlr_gamma_control_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrGammaControlV1,
+        data: &Option<Output>,
+    ) {
+        let controls = &mut state.gamma_control_state().controls;
+        let len = controls.len();
+        controls.retain(|control| control != resource);
+        if controls.len() != len {
+            if let Some(output) = data {
+                state.set_gamma(output, None);
+            }
+        }
+    }
+}
+
+/// Implement the gamma control protocol for the given state type
+#[macro_export]
+macro_rules! delegate_gamma_control {
+    ($ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: $crate::gamma_control::GammaControlManagerGlobalData
+        ] => $crate::gamma_control::GammaControlManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::gamma_control::GammaControlManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_v1::ZwlrGammaControlV1: Option<smithay::output::Output>
+        ] => $crate::gamma_control::GammaControlManagerState);
+    };
+}
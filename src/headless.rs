@@ -0,0 +1,318 @@
+use std::{str::FromStr, sync::atomic::Ordering, time::Duration};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        egl::{EGLContext, EGLDevice, EGLDisplay},
+        renderer::{
+            damage::OutputDamageTracker,
+            gles::{GlesRenderbuffer, GlesRenderer, GlesTexture},
+            Bind, Offscreen,
+        },
+    },
+    desktop::Space,
+    output::{Mode, Output, PhysicalProperties, Subpixel},
+    reexports::{
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            EventLoop,
+        },
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
+        wayland_server::{protocol::wl_surface, Display},
+    },
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    client::ClientSupervisor,
+    config::{reload_on_sighup, ColorConfig, Config, ReloadReport},
+    render::{output_elements, CustomRenderElements},
+    screenshot::{render_screenshot, screenshot_on_sigusr1, Screenshot},
+    shell::WindowElement,
+    state::{post_repaint, take_presentation_feedback, Backend, BuedchenState, CalloopData},
+};
+
+/// Size and refresh rate of a virtual output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeadlessOutputConfig {
+    pub width: i32,
+    pub height: i32,
+    /// Refresh rate in mHz
+    pub refresh: i32,
+}
+
+impl FromStr for HeadlessOutputConfig {
+    type Err = String;
+
+    /// Parses `WIDTHxHEIGHT` or `WIDTHxHEIGHT@REFRESH`, e.g. `1920x1080@60`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (size, refresh) = match s.split_once('@') {
+            Some((size, refresh)) => (
+                size,
+                refresh
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid refresh rate: {}", refresh))?,
+            ),
+            None => (s, 60.0),
+        };
+        let (width, height) = size
+            .split_once('x')
+            .ok_or_else(|| format!("expected WIDTHxHEIGHT, got {}", size))?;
+        let width = width
+            .parse::<i32>()
+            .map_err(|_| format!("invalid width: {}", width))?;
+        let height = height
+            .parse::<i32>()
+            .map_err(|_| format!("invalid height: {}", height))?;
+        if width <= 0 || height <= 0 || refresh <= 0.0 {
+            return Err(format!(
+                "output size and refresh rate must be positive: {}",
+                s
+            ));
+        }
+
+        Ok(HeadlessOutputConfig {
+            width,
+            height,
+            refresh: (refresh * 1000.0).round() as i32,
+        })
+    }
+}
+
+struct HeadlessSurface {
+    output: Output,
+    buffer: GlesRenderbuffer,
+    damage_tracker: OutputDamageTracker,
+    age: usize,
+}
+
+pub struct HeadlessData {
+    renderer: GlesRenderer,
+    surfaces: Vec<HeadlessSurface>,
+}
+
+impl std::fmt::Debug for HeadlessData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeadlessData")
+            .field(
+                "outputs",
+                &self
+                    .surfaces
+                    .iter()
+                    .map(|surface| surface.output.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Backend for HeadlessData {
+    fn seat_name(&self) -> String {
+        String::from("headless")
+    }
+
+    fn reset_buffers(&mut self, output: &Output) {
+        if let Some(surface) = self.surfaces.iter_mut().find(|s| &s.output == output) {
+            surface.age = 0;
+        }
+    }
+
+    fn early_import(&mut self, _surface: &wl_surface::WlSurface) {}
+
+    fn render_screenshot(
+        &mut self,
+        output: &Output,
+        space: &Space<WindowElement>,
+        show_window_preview: bool,
+        colors: &ColorConfig,
+    ) -> Result<Screenshot, Box<dyn std::error::Error>> {
+        render_screenshot::<_, GlesTexture>(
+            &mut self.renderer,
+            output,
+            space,
+            show_window_preview,
+            colors,
+        )
+    }
+}
+
+fn software_renderer() -> Result<GlesRenderer, Box<dyn std::error::Error>> {
+    let mut devices = EGLDevice::enumerate()?.collect::<Vec<_>>();
+    let device = match devices.iter().position(EGLDevice::is_software) {
+        Some(index) => devices.swap_remove(index),
+        None => {
+            warn!("No software EGL device found, falling back to the first available device");
+            devices.into_iter().next().ok_or("No EGL device found")?
+        }
+    };
+
+    let display = EGLDisplay::new(device)?;
+    let context = EGLContext::new(&display)?;
+    // Safety: the context is only ever used by this renderer
+    Ok(unsafe { GlesRenderer::new(context)? })
+}
+
+pub fn run_headless(client: ClientSupervisor, config: Config, outputs: &[HeadlessOutputConfig]) {
+    let mut event_loop = EventLoop::try_new().unwrap();
+    let display = Display::new().unwrap();
+    let mut display_handle = display.handle();
+
+    let mut renderer = match software_renderer() {
+        Ok(renderer) => renderer,
+        Err(err) => {
+            error!("Failed to initialize the software renderer: {}", err);
+            return;
+        }
+    };
+
+    let mut surfaces = Vec::new();
+    let mut x = 0;
+    for (index, config) in outputs.iter().enumerate() {
+        let mode = Mode {
+            size: (config.width, config.height).into(),
+            refresh: config.refresh,
+        };
+        let output = Output::new(
+            format!("HEADLESS-{}", index + 1),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "buedchen".into(),
+                model: "headless".into(),
+            },
+        );
+        output.set_preferred(mode);
+        output.change_current_state(Some(mode), None, None, Some((x, 0).into()));
+        x += config.width;
+
+        let buffer =
+            match renderer.create_buffer(Fourcc::Abgr8888, (config.width, config.height).into()) {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    error!(?err, "Failed to create offscreen buffer");
+                    return;
+                }
+            };
+        surfaces.push(HeadlessSurface {
+            damage_tracker: OutputDamageTracker::from_output(&output),
+            output,
+            buffer,
+            age: 0,
+        });
+    }
+
+    let data = HeadlessData { renderer, surfaces };
+    let mut state = BuedchenState::init(display, event_loop.handle(), data, true, client, config);
+
+    for surface in &state.backend_data.surfaces {
+        let output = surface.output.clone();
+        output.create_global::<BuedchenState<HeadlessData>>(&display_handle);
+        state.space.map_output(&output, output.current_location());
+        info!(name = output.name(), "Added headless output");
+
+        let frame_duration = output
+            .current_mode()
+            .map(|mode| Duration::from_secs_f64(1_000f64 / mode.refresh as f64))
+            .unwrap();
+        event_loop
+            .handle()
+            .insert_source(Timer::immediate(), move |_, _, data| {
+                data.state.render_headless(&output);
+                TimeoutAction::ToDuration(frame_duration)
+            })
+            .expect("failed to schedule frame timer");
+    }
+
+    if let Err(err) = reload_on_sighup(&mut state, BuedchenState::reload_headless_config) {
+        warn!("Unable to reload the configuration on SIGHUP: {}", err);
+    }
+    if let Err(err) = screenshot_on_sigusr1(&event_loop.handle()) {
+        warn!("Unable to take screenshots on SIGUSR1: {}", err);
+    }
+
+    if let Err(e) = state.start_client() {
+        error!("Couldn't start client: {}", e);
+        return;
+    }
+    info!("Client started successfully");
+
+    while state.running.load(Ordering::SeqCst) {
+        let mut calloop_data = CalloopData {
+            state,
+            display_handle,
+        };
+        let result = event_loop.dispatch(Some(Duration::from_millis(16)), &mut calloop_data);
+        CalloopData {
+            state,
+            display_handle,
+        } = calloop_data;
+
+        if result.is_err() {
+            state.running.store(false, Ordering::SeqCst);
+        } else {
+            state.space.refresh();
+            state.popups.cleanup();
+            display_handle.flush_clients().unwrap();
+        }
+
+        state.check_client();
+    }
+}
+
+impl BuedchenState<HeadlessData> {
+    fn reload_headless_config(&mut self) -> Result<ReloadReport, String> {
+        let (_, mut report) = self.reload_config()?;
+        // virtual outputs are configured on the command line, there is no cursor and no input
+        report.not_applied(&["output", "cursor", "idle", "schedule", "inputs"]);
+        Ok(report)
+    }
+
+    fn render_headless(&mut self, output: &Output) {
+        let backend = &mut self.backend_data;
+        let Some(surface) = backend.surfaces.iter_mut().find(|s| &s.output == output) else {
+            return;
+        };
+
+        if let Err(err) = backend.renderer.bind(surface.buffer.clone()) {
+            warn!(?err, "Failed to bind offscreen buffer");
+            return;
+        }
+
+        let (elements, clear_color) = output_elements(
+            output,
+            &self.space,
+            Vec::<CustomRenderElements<_>>::new(),
+            &mut backend.renderer,
+            self.show_window_preview,
+            &self.config.colors,
+        );
+        let res = match surface.damage_tracker.render_output(
+            &mut backend.renderer,
+            surface.age,
+            &elements,
+            clear_color,
+        ) {
+            Ok(res) => res,
+            Err(err) => {
+                warn!(?err, "Error during rendering");
+                return;
+            }
+        };
+        // the same buffer is reused for every frame
+        surface.age = 1;
+
+        post_repaint(output, &res.states, &self.space, None, self.clock.now());
+        if res.damage.is_some() {
+            let mut feedback = take_presentation_feedback(output, &self.space, &res.states);
+            feedback.presented(
+                self.clock.now(),
+                output
+                    .current_mode()
+                    .map(|mode| Duration::from_secs_f64(1_000f64 / mode.refresh as f64))
+                    .unwrap_or_default(),
+                0,
+                wp_presentation_feedback::Kind::empty(),
+            );
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Dimming and blanking of the outputs after a period without input
+//!
+//! The timer only runs until the next configured timeout or idle notification, every input
+//! restarts the timeouts. While a visible surface inhibits idling the seat counts as active.
+//! The input waking blanked outputs is not handed to the clients, neither is the release
+//! belonging to it.
+
+use std::time::{Duration, Instant};
+
+use smithay::{
+    backend::input::{
+        ButtonState, InputBackend, InputEvent, KeyState, KeyboardKeyEvent, PointerButtonEvent,
+        TouchEvent, TouchSlot,
+    },
+    desktop::{layer_map_for_output, WindowSurfaceType},
+    reexports::{
+        calloop::{
+            timer::{TimeoutAction, Timer},
+            RegistrationToken,
+        },
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
+    wayland::compositor::get_parent,
+};
+use tracing::{info, warn};
+
+use crate::state::{Backend, BuedchenState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IdlePhase {
+    Active,
+    Dimmed,
+    Blanked,
+}
+
+/// A press that woke the outputs, whose release is swallowed as well
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WakeInput {
+    Key(u32),
+    Button(u32),
+    Touch(TouchSlot),
+}
+
+#[derive(Debug)]
+pub struct IdleState {
+    last_activity: Instant,
+    phase: IdlePhase,
+    timer: Option<RegistrationToken>,
+    wake_input: Option<WakeInput>,
+}
+
+impl Default for IdleState {
+    fn default() -> Self {
+        IdleState {
+            last_activity: Instant::now(),
+            phase: IdlePhase::Active,
+            timer: None,
+            wake_input: None,
+        }
+    }
+}
+
+/// Whether an event was caused by someone using an input device
+pub fn is_user_input<B: InputBackend>(event: &InputEvent<B>) -> bool {
+    !matches!(
+        event,
+        InputEvent::DeviceAdded { .. } | InputEvent::DeviceRemoved { .. }
+    )
+}
+
+/// The press an event starts, if any
+fn pressed_input<B: InputBackend>(event: &InputEvent<B>) -> Option<WakeInput> {
+    match event {
+        InputEvent::Keyboard { event } if event.state() == KeyState::Pressed => {
+            Some(WakeInput::Key(event.key_code()))
+        }
+        InputEvent::PointerButton { event } if event.state() == ButtonState::Pressed => {
+            Some(WakeInput::Button(event.button_code()))
+        }
+        InputEvent::TouchDown { event } => Some(WakeInput::Touch(event.slot())),
+        _ => None,
+    }
+}
+
+/// Whether an event continues `wake`, and whether it also ends it
+fn continues<B: InputBackend>(wake: WakeInput, event: &InputEvent<B>) -> Option<bool> {
+    match (wake, event) {
+        (WakeInput::Key(code), InputEvent::Keyboard { event }) if event.key_code() == code => {
+            Some(event.state() == KeyState::Released)
+        }
+        (WakeInput::Button(code), InputEvent::PointerButton { event })
+            if event.button_code() == code =>
+        {
+            Some(event.state() == ButtonState::Released)
+        }
+        (WakeInput::Touch(slot), InputEvent::TouchMotion { event }) if event.slot() == slot => {
+            Some(false)
+        }
+        (WakeInput::Touch(slot), InputEvent::TouchUp { event }) if event.slot() == slot => {
+            Some(true)
+        }
+        (WakeInput::Touch(_), InputEvent::TouchCancel { .. }) => Some(true),
+        _ => None,
+    }
+}
+
+impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
+    /// Restart the idle timeouts, returns whether blanked outputs were woken up
+    pub fn notify_activity(&mut self) -> bool {
+        let phase = self.idle.phase;
+        self.reset_idle();
+        // a running timer checks the new deadline once it fires
+        if phase != IdlePhase::Active || self.idle.timer.is_none() {
+            self.arm_idle_timer();
+        }
+        phase == IdlePhase::Blanked
+    }
+
+    fn reset_idle(&mut self) {
+        self.idle.last_activity = Instant::now();
+        self.idle_notifier_state.activity();
+        if self.idle.phase != IdlePhase::Active {
+            self.set_idle_phase(IdlePhase::Active);
+        }
+    }
+
+    /// Handle the idle side of an input event, returns `true` if the event must not be processed
+    pub fn swallow_wake_input<B: InputBackend>(&mut self, event: &InputEvent<B>) -> bool {
+        if !is_user_input(event) {
+            return false;
+        }
+        // the outputs stay off outside of the schedule, the input still counts as activity
+        if self.schedule.is_off() {
+            self.notify_activity();
+            return false;
+        }
+
+        if let Some(wake) = self.idle.wake_input {
+            if let Some(finished) = continues(wake, event) {
+                if finished {
+                    self.idle.wake_input = None;
+                }
+                self.notify_activity();
+                return true;
+            }
+        }
+
+        if self.notify_activity() {
+            self.idle.wake_input = pressed_input(event);
+            return true;
+        }
+        false
+    }
+
+    /// (Re)start the timer for the next configured idle timeout
+    pub fn arm_idle_timer(&mut self) {
+        if let Some(token) = self.idle.timer.take() {
+            self.handle.remove(token);
+        }
+        let Some(timeout) = self.next_idle_timeout() else {
+            return;
+        };
+
+        let res = self
+            .handle
+            .insert_source(Timer::from_duration(timeout), |_, _, data| {
+                match data.state.idle_timeout() {
+                    Some(next) => TimeoutAction::ToDuration(next),
+                    None => {
+                        data.state.idle.timer = None;
+                        TimeoutAction::Drop
+                    }
+                }
+            });
+        match res {
+            Ok(token) => self.idle.timer = Some(token),
+            Err(err) => warn!("Failed to start the idle timer: {}", err),
+        }
+    }
+
+    /// Time until the next phase is reached, `None` if there is no further phase
+    fn next_idle_timeout(&self) -> Option<Duration> {
+        let elapsed = self.idle.last_activity.elapsed();
+        [
+            (IdlePhase::Dimmed, self.config.idle.dim_after),
+            (IdlePhase::Blanked, self.config.idle.blank_after),
+        ]
+        .into_iter()
+        .filter(|(phase, _)| *phase > self.idle.phase)
+        .filter_map(|(_, after)| after)
+        .map(|after| Duration::from_secs(after).saturating_sub(elapsed))
+        .chain(self.idle_notifier_state.next_timeout(elapsed))
+        .min()
+    }
+
+    fn idle_timeout(&mut self) -> Option<Duration> {
+        if self.idle_inhibited() {
+            self.reset_idle();
+            return self.next_idle_timeout();
+        }
+
+        let elapsed = self.idle.last_activity.elapsed();
+        self.idle_notifier_state.check(elapsed);
+        let reached =
+            |after: Option<u64>| after.map_or(false, |after| elapsed >= Duration::from_secs(after));
+        if self.idle.phase < IdlePhase::Blanked && reached(self.config.idle.blank_after) {
+            self.set_idle_phase(IdlePhase::Blanked);
+        } else if self.idle.phase < IdlePhase::Dimmed && reached(self.config.idle.dim_after) {
+            self.set_idle_phase(IdlePhase::Dimmed);
+        }
+        self.next_idle_timeout()
+    }
+
+    /// Whether a surface with an idle inhibitor is shown on an output
+    fn idle_inhibited(&self) -> bool {
+        self.idle_inhibit_state
+            .surfaces()
+            .any(|surface| self.surface_visible(surface))
+    }
+
+    fn surface_visible(&self, surface: &WlSurface) -> bool {
+        let mut root = surface.clone();
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+
+        self.space.elements().any(|window| {
+            window.0.toplevel().wl_surface() == &root
+                && !self.space.outputs_for_element(window).is_empty()
+        }) || self.space.outputs().any(|output| {
+            layer_map_for_output(output)
+                .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                .is_some()
+        })
+    }
+
+    fn set_idle_phase(&mut self, phase: IdlePhase) {
+        info!(?phase, "Idle state changed");
+        let dim = match phase {
+            IdlePhase::Dimmed => self.config.idle.dim_level.clamp(0.0, 1.0),
+            IdlePhase::Active | IdlePhase::Blanked => 0.0,
+        };
+        self.backend_data.set_dim(dim);
+        self.idle.phase = phase;
+        self.update_blanking();
+    }
+
+    /// Turn the outputs off while idle or outside of the schedule
+    pub fn update_blanking(&mut self) {
+        let blanked = self.idle.phase == IdlePhase::Blanked || self.schedule.is_off();
+        self.backend_data.set_blanked(blanked);
+    }
+}
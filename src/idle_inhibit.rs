@@ -0,0 +1,148 @@
+//! Server side of the `idle-inhibit-unstable-v1` protocol
+//!
+//! The protocol state only keeps track of the surfaces with an inhibitor, whether one of them
+//! is visible and actually prevents idling is decided by the compositor.
+
+use smithay::reexports::{
+    wayland_protocols::wp::idle_inhibit::zv1::server::{
+        zwp_idle_inhibit_manager_v1::{self, ZwpIdleInhibitManagerV1},
+        zwp_idle_inhibitor_v1::{self, ZwpIdleInhibitorV1},
+    },
+    wayland_server::{
+        backend::{ClientId, GlobalId},
+        protocol::wl_surface::WlSurface,
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+};
+
+const VERSION: u32 = 1;
+
+pub trait IdleInhibitHandler {
+    fn idle_inhibit_state(&mut self) -> &mut IdleInhibitManagerState;
+}
+
+#[derive(Debug)]
+pub struct IdleInhibitManagerState {
+    global: GlobalId,
+    inhibitors: Vec<ZwpIdleInhibitorV1>,
+}
+
+impl IdleInhibitManagerState {
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ZwpIdleInhibitManagerV1, ()>
+            + Dispatch<ZwpIdleInhibitManagerV1, ()>
+            + 'static,
+    {
+        let global = display.create_global::<D, ZwpIdleInhibitManagerV1, _>(VERSION, ());
+        IdleInhibitManagerState {
+            global,
+            inhibitors: Vec::new(),
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Surfaces asking for the seat not to become idle while they are visible
+    pub fn surfaces(&self) -> impl Iterator<Item = &WlSurface> {
+        self.inhibitors
+            .iter()
+            .filter_map(|inhibitor| inhibitor.data::<WlSurface>())
+            .filter(|surface| surface.is_alive())
+    }
+}
+
+impl<D> GlobalDispatch<ZwpIdleInhibitManagerV1, (), D> for IdleInhibitManagerState
+where
+    D: GlobalDispatch<ZwpIdleInhibitManagerV1, ()>
+        + Dispatch<ZwpIdleInhibitManagerV1, ()>
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwpIdleInhibitManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ZwpIdleInhibitManagerV1, (), D> for IdleInhibitManagerState
+where
+    D: Dispatch<ZwpIdleInhibitManagerV1, ()>
+        + Dispatch<ZwpIdleInhibitorV1, WlSurface>
+        + IdleInhibitHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZwpIdleInhibitManagerV1,
+        request: zwp_idle_inhibit_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwp_idle_inhibit_manager_v1::Request::CreateInhibitor { id, surface } => {
+                let inhibitor = data_init.init(id, surface);
+                state.idle_inhibit_state().inhibitors.push(inhibitor);
+            }
+            zwp_idle_inhibit_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwpIdleInhibitorV1, WlSurface, D> for IdleInhibitManagerState
+where
+    D: Dispatch<ZwpIdleInhibitorV1, WlSurface> + IdleInhibitHandler + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZwpIdleInhibitorV1,
+        request: zwp_idle_inhibitor_v1::Request,
+        _data: &WlSurface,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwp_idle_inhibitor_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwpIdleInhibitorV1,
+        _data: &WlSurface,
+    ) {
+        state
+            .idle_inhibit_state()
+            .inhibitors
+            .retain(|inhibitor| inhibitor != resource);
+    }
+}
+
+/// Implement the idle inhibit protocol for the given state type
+#[macro_export]
+macro_rules! delegate_idle_inhibit {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::idle_inhibit::zv1::server::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1: ()
+        ] => $crate::idle_inhibit::IdleInhibitManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::idle_inhibit::zv1::server::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1: ()
+        ] => $crate::idle_inhibit::IdleInhibitManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::idle_inhibit::zv1::server::zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1: smithay::reexports::wayland_server::protocol::wl_surface::WlSurface
+        ] => $crate::idle_inhibit::IdleInhibitManagerState);
+    };
+}
@@ -0,0 +1,191 @@
+//! Server side of the `ext-idle-notify-v1` protocol
+//!
+//! The protocol state only keeps the notifications and their timeouts. When the seat became
+//! idle is tracked by the compositor, which reports it through [`IdleNotifierState::check`]
+//! and [`IdleNotifierState::activity`].
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use smithay::reexports::{
+    wayland_protocols::ext::idle_notify::v1::server::{
+        ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+        ext_idle_notifier_v1::{self, ExtIdleNotifierV1},
+    },
+    wayland_server::{
+        backend::{ClientId, GlobalId},
+        Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    },
+};
+
+const VERSION: u32 = 1;
+
+pub trait IdleNotifyHandler {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState;
+
+    /// A client asked for a new notification, its timeout has to be scheduled
+    fn idle_notification_created(&mut self);
+}
+
+pub struct IdleNotificationData {
+    timeout: Duration,
+    idle: AtomicBool,
+}
+
+#[derive(Debug)]
+pub struct IdleNotifierState {
+    global: GlobalId,
+    notifications: Vec<ExtIdleNotificationV1>,
+}
+
+impl IdleNotifierState {
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ExtIdleNotifierV1, ()> + Dispatch<ExtIdleNotifierV1, ()> + 'static,
+    {
+        let global = display.create_global::<D, ExtIdleNotifierV1, _>(VERSION, ());
+        IdleNotifierState {
+            global,
+            notifications: Vec::new(),
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Time until the next notification becomes idle, given the time since the last input
+    pub fn next_timeout(&self, elapsed: Duration) -> Option<Duration> {
+        self.notifications
+            .iter()
+            .filter_map(|notification| notification.data::<IdleNotificationData>())
+            .filter(|data| !data.idle.load(Ordering::SeqCst))
+            .map(|data| data.timeout.saturating_sub(elapsed))
+            .min()
+    }
+
+    /// Notify the clients whose timeout passed, given the time since the last input
+    pub fn check(&mut self, elapsed: Duration) {
+        for notification in &self.notifications {
+            let Some(data) = notification.data::<IdleNotificationData>() else {
+                continue;
+            };
+            if elapsed >= data.timeout && !data.idle.swap(true, Ordering::SeqCst) {
+                notification.idled();
+            }
+        }
+    }
+
+    /// Notify the clients that were told about idleness that there was input again
+    pub fn activity(&mut self) {
+        for notification in &self.notifications {
+            let Some(data) = notification.data::<IdleNotificationData>() else {
+                continue;
+            };
+            if data.idle.swap(false, Ordering::SeqCst) {
+                notification.resumed();
+            }
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ExtIdleNotifierV1, (), D> for IdleNotifierState
+where
+    D: GlobalDispatch<ExtIdleNotifierV1, ()> + Dispatch<ExtIdleNotifierV1, ()> + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtIdleNotifierV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl<D> Dispatch<ExtIdleNotifierV1, (), D> for IdleNotifierState
+where
+    D: Dispatch<ExtIdleNotifierV1, ()>
+        + Dispatch<ExtIdleNotificationV1, IdleNotificationData>
+        + IdleNotifyHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ExtIdleNotifierV1,
+        request: ext_idle_notifier_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            // there is a single seat, so the seat is not looked at
+            ext_idle_notifier_v1::Request::GetIdleNotification { id, timeout, .. } => {
+                let notification = data_init.init(
+                    id,
+                    IdleNotificationData {
+                        timeout: Duration::from_millis(timeout.into()),
+                        idle: AtomicBool::new(false),
+                    },
+                );
+                state.idle_notifier_state().notifications.push(notification);
+                state.idle_notification_created();
+            }
+            ext_idle_notifier_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ExtIdleNotificationV1, IdleNotificationData, D> for IdleNotifierState
+where
+    D: Dispatch<ExtIdleNotificationV1, IdleNotificationData> + IdleNotifyHandler + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ExtIdleNotificationV1,
+        request: ext_idle_notification_v1::Request,
+        _data: &IdleNotificationData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_idle_notification_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ExtIdleNotificationV1,
+        _data: &IdleNotificationData,
+    ) {
+        state
+            .idle_notifier_state()
+            .notifications
+            .retain(|notification| notification != resource);
+    }
+}
+
+/// Implement the idle notify protocol for the given state type
+#[macro_export]
+macro_rules! delegate_idle_notify {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::idle_notify::v1::server::ext_idle_notifier_v1::ExtIdleNotifierV1: ()
+        ] => $crate::idle_notify::IdleNotifierState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::idle_notify::v1::server::ext_idle_notifier_v1::ExtIdleNotifierV1: ()
+        ] => $crate::idle_notify::IdleNotifierState);
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::idle_notify::v1::server::ext_idle_notification_v1::ExtIdleNotificationV1: $crate::idle_notify::IdleNotificationData
+        ] => $crate::idle_notify::IdleNotifierState);
+    };
+}
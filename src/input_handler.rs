@@ -1,8 +1,17 @@
 use std::{convert::TryInto, sync::atomic::Ordering};
 
-use crate::{focus::FocusTarget, shell::FullscreenSurface, BuedchenState};
+use crate::{
+    config::{Action, KeyCombo, ShortcutConfig},
+    focus::FocusTarget,
+    idle::is_user_input,
+    shell::FullscreenSurface,
+    BuedchenState,
+};
 
-use crate::udev::UdevData;
+use crate::{
+    render::mirror_placement,
+    udev::{InputTarget, UdevData},
+};
 
 use smithay::{
     backend::input::{
@@ -14,8 +23,9 @@ use smithay::{
         keyboard::{keysyms as xkb, FilterResult, Keysym, ModifiersState},
         pointer::{AxisFrame, ButtonEvent, MotionEvent},
     },
+    output::Output,
     reexports::wayland_server::{protocol::wl_pointer, DisplayHandle},
-    utils::{Logical, Point, Serial, SERIAL_COUNTER as SCOUNTER},
+    utils::{Logical, Physical, Point, Serial, Size, SERIAL_COUNTER as SCOUNTER},
     wayland::{
         compositor::with_states,
         input_method::InputMethodSeat,
@@ -26,7 +36,7 @@ use smithay::{
 
 use smithay::backend::input::{AbsolutePositionEvent, TouchEvent as _};
 
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::state::Backend;
 use smithay::{
@@ -51,7 +61,7 @@ use smithay::{
     },
 };
 
-impl<BackendData: Backend> BuedchenState<BackendData> {
+impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
     fn keyboard_key_to_action<B: InputBackend>(&mut self, evt: B::KeyboardKeyEvent) -> KeyAction {
         let keycode = evt.key_code();
         let state = evt.state();
@@ -59,7 +69,17 @@ impl<BackendData: Backend> BuedchenState<BackendData> {
         let serial = SCOUNTER.next_serial();
         let time = Event::time_msec(&evt);
         let mut suppressed_keys = self.suppressed_keys.clone();
+        let shortcuts = self.config.shortcuts.clone();
         let keyboard = self.seat.get_keyboard().unwrap();
+        // nobody is supposed to use the clients outside of the schedule, bindings still work
+        let schedule_off = self.schedule.is_off();
+        let forward = move || {
+            if schedule_off {
+                FilterResult::Intercept(KeyAction::None)
+            } else {
+                FilterResult::Forward
+            }
+        };
 
         for layer in self.layer_shell_state.layer_surfaces().rev() {
             let data = with_states(layer.wl_surface(), |states| {
@@ -67,6 +87,7 @@ impl<BackendData: Backend> BuedchenState<BackendData> {
             });
             if data.keyboard_interactivity == KeyboardInteractivity::Exclusive
                 && (data.layer == WlrLayer::Top || data.layer == WlrLayer::Overlay)
+                && !schedule_off
             {
                 let surface = self.space.outputs().find_map(|o| {
                     let map = layer_map_for_output(o);
@@ -100,7 +121,7 @@ impl<BackendData: Backend> BuedchenState<BackendData> {
                 state,
                 serial,
                 time,
-                |_, modifiers, handle| {
+                |data, modifiers, handle| {
                     let keysym = handle.modified_sym();
 
                     debug!(
@@ -116,26 +137,27 @@ impl<BackendData: Backend> BuedchenState<BackendData> {
                     // so that we can decide on a release if the key
                     // should be forwarded to the client or not.
                     if let KeyState::Pressed = state {
-                        if !inhibited {
-                            let action = process_keyboard_shortcut(*modifiers, keysym);
+                        data.lockdown_key_pressed(modifiers, keysym, keycode);
+                        if !inhibited && !data.shortcuts_locked() {
+                            let action =
+                                process_keyboard_shortcut(&shortcuts, *modifiers, keysym, keycode);
 
                             if action.is_some() {
-                                suppressed_keys.push(keysym);
+                                suppressed_keys.push(keycode);
                             }
 
-                            action
-                                .map(FilterResult::Intercept)
-                                .unwrap_or(FilterResult::Forward)
+                            action.map(FilterResult::Intercept).unwrap_or_else(forward)
                         } else {
-                            FilterResult::Forward
+                            forward()
                         }
                     } else {
-                        let suppressed = suppressed_keys.contains(&keysym);
+                        data.lockdown_key_released(keycode);
+                        let suppressed = suppressed_keys.contains(&keycode);
                         if suppressed {
-                            suppressed_keys.retain(|k| *k != keysym);
+                            suppressed_keys.retain(|k| *k != keycode);
                             FilterResult::Intercept(KeyAction::None)
                         } else {
-                            FilterResult::Forward
+                            forward()
                         }
                     }
                 },
@@ -146,6 +168,80 @@ impl<BackendData: Backend> BuedchenState<BackendData> {
         action
     }
 
+    /// Run the action of a key binding
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Quit => {
+                info!("Quitting.");
+                self.running.store(false, Ordering::SeqCst);
+            }
+            Action::RestartClient { output } => {
+                if let Err(err) = self
+                    .client_slot(output.as_deref())
+                    .and_then(|slot| self.restart_client(slot))
+                {
+                    warn!(output, "Couldn't restart client: {}", err);
+                }
+            }
+            Action::Screenshot => self.screenshot_all(),
+            Action::ToggleOutputPower { output } => {
+                let outputs = self
+                    .space
+                    .outputs()
+                    .filter(|o| output.as_ref().map_or(true, |name| o.name() == *name))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if outputs.is_empty() {
+                    warn!(output, "No output to toggle the power of");
+                    return;
+                }
+                // if any of them is on, turn all of them off
+                let on = !outputs
+                    .iter()
+                    .any(|o| self.backend_data.is_output_powered(o));
+                for o in &outputs {
+                    if !self.backend_data.set_output_power(o, on) {
+                        warn!(
+                            output = o.name(),
+                            "Output power can't be changed on this backend"
+                        );
+                    }
+                }
+            }
+            Action::CycleFocus => {
+                let keyboard = self.seat.get_keyboard().unwrap();
+                let windows = self.space.elements().cloned().collect::<Vec<_>>();
+                if windows.is_empty() {
+                    return;
+                }
+                // windows are ordered from bottom to top, raising the next one rotates them all
+                let current = match keyboard.current_focus() {
+                    Some(FocusTarget::Window(focused)) => {
+                        windows.iter().position(|window| *window == focused)
+                    }
+                    _ => None,
+                };
+                let next = current.map_or(0, |index| (index + 1) % windows.len());
+                let window = windows[next].clone();
+                self.space.raise_element(&window, true);
+                keyboard.set_focus(self, Some(window.into()), SCOUNTER.next_serial());
+            }
+            Action::Spawn { command } => {
+                if let Err(err) = self.spawn_helper(&command) {
+                    error!(?command, "Couldn't spawn helper: {}", err);
+                }
+            }
+            Action::NextKeyboardLayout => self.next_keyboard_layout(),
+            Action::ToggleWindowPreview => {
+                self.show_window_preview = !self.show_window_preview;
+                info!(show = self.show_window_preview, "Toggled window preview");
+                for output in self.space.outputs().cloned().collect::<Vec<_>>() {
+                    self.backend_data.reset_buffers(&output);
+                }
+            }
+        }
+    }
+
     fn on_pointer_button<B: InputBackend>(&mut self, evt: B::PointerButtonEvent) {
         let serial = SCOUNTER.next_serial();
         let button = evt.button_code();
@@ -323,28 +419,134 @@ impl<BackendData: Backend> BuedchenState<BackendData> {
             pointer.frame(self);
         }
     }
-}
 
-impl BuedchenState<UdevData> {
-    /// Apply an output transform to a point.
-    fn transform_position<I, E>(&self, event: &E) -> Point<f64, Logical>
-    where
-        E: AbsolutePositionEvent<I>,
-        I: InputBackend,
-    {
-        let max_x = self.space.outputs().fold(0, |acc, o| {
-            acc + self.space.output_geometry(o).unwrap().size.w
-        });
+    /// Handle input of a backend running inside a window of another compositor,
+    /// absolute pointer positions are relative to the output named `output_name`
+    pub fn process_input_event_windowed<B: InputBackend>(
+        &mut self,
+        dh: &DisplayHandle,
+        event: InputEvent<B>,
+        output_name: &str,
+    ) {
+        if is_user_input(&event) {
+            self.notify_activity();
+        }
 
-        let max_h_output = self
-            .space
-            .outputs()
-            .max_by_key(|o| self.space.output_geometry(o).unwrap().size.h)
-            .unwrap();
+        match event {
+            InputEvent::Keyboard { event, .. } => match self.keyboard_key_to_action::<B>(event) {
+                KeyAction::Run(action) => self.run_action(action),
+                KeyAction::None => (),
+                action => {
+                    warn!(?action, "Key action unsupported on this backend");
+                }
+            },
+            InputEvent::PointerMotionAbsolute { event, .. } => {
+                let output = self
+                    .space
+                    .outputs()
+                    .find(|o| o.name() == output_name)
+                    .cloned();
+                if let Some(output) = output {
+                    self.on_pointer_move_absolute_windowed::<B>(event, &output);
+                }
+            }
+            InputEvent::PointerButton { event, .. } => self.on_pointer_button::<B>(event),
+            InputEvent::PointerAxis { event, .. } => self.on_pointer_axis::<B>(dh, event),
+            _ => (), // other events are not handled on nested backends
+        }
+    }
+
+    fn on_pointer_move_absolute_windowed<B: InputBackend>(
+        &mut self,
+        evt: B::PointerMotionAbsoluteEvent,
+        output: &Output,
+    ) {
+        let output_geo = self.space.output_geometry(output).unwrap();
 
-        let max_y = self.space.output_geometry(max_h_output).unwrap().size.h;
+        let pos = evt.position_transformed(output_geo.size) + output_geo.loc.to_f64();
+        let serial = SCOUNTER.next_serial();
+
+        let pointer = self.pointer.clone();
+        let under = self.surface_under(pos);
+        pointer.motion(
+            self,
+            under,
+            &MotionEvent {
+                location: pos,
+                serial,
+                time: evt.time_msec(),
+            },
+        );
+        pointer.frame(self);
+    }
+}
 
-        (event.x_transformed(max_x), event.y_transformed(max_y)).into()
+impl BuedchenState<UdevData> {
+    /// Map an absolute position of `device` onto its output, following the output's transform
+    ///
+    /// Devices report positions relative to the panel as it is mounted, so a rotated
+    /// output needs the position rotated the same way. `position_transformed` scales
+    /// the device position to the given size, the logical size of the output already
+    /// accounts for its scale. Positions on a mirror end up where the mirrored content
+    /// is on its source. Devices without an output of their own span all outputs.
+    fn transform_position(
+        &self,
+        device: &impl Device,
+        position_transformed: impl FnOnce(Size<i32, Logical>) -> Point<f64, Logical>,
+    ) -> Option<Point<f64, Logical>> {
+        let target = match self.input_target(&device.id())? {
+            // a single output still rotates the input with it
+            InputTarget::Layout if self.space.outputs().count() == 1 => {
+                InputTarget::Output(self.space.outputs().next()?.clone())
+            }
+            target => target,
+        };
+        match target {
+            InputTarget::Output(output) => {
+                let output_geo = self.space.output_geometry(&output)?;
+                let transform = output.current_transform();
+
+                let size = transform.invert().transform_size(output_geo.size);
+                let position =
+                    transform.transform_point_in(position_transformed(size), &size.to_f64());
+                Some(position + output_geo.loc.to_f64())
+            }
+            InputTarget::Mirror {
+                output,
+                source,
+                scaling,
+            } => {
+                let source_geo = self.space.output_geometry(&source)?;
+                let (scale, offset) = mirror_placement(&source, &output, scaling)?;
+
+                // a mirror has no logical size, map onto its pixels instead
+                let mode_size = output.current_mode()?.size;
+                let size = Size::<i32, Logical>::from((mode_size.w, mode_size.h));
+                let position = output
+                    .current_transform()
+                    .transform_point_in(position_transformed(size), &size.to_f64());
+                let position = Point::<f64, Physical>::from((
+                    (position.x - offset.x) / scale.x,
+                    (position.y - offset.y) / scale.y,
+                ))
+                .to_logical(source.current_scale().fractional_scale());
+
+                // positions on the letterbox bars end up on the closest edge of the source
+                let max = source_geo.size.to_f64();
+                let position =
+                    Point::from((position.x.clamp(0.0, max.w), position.y.clamp(0.0, max.h)));
+                Some(position + source_geo.loc.to_f64())
+            }
+            InputTarget::Layout => {
+                let layout = self
+                    .space
+                    .outputs()
+                    .filter_map(|output| self.space.output_geometry(output))
+                    .reduce(|layout, geometry| layout.merge(geometry))?;
+                // the bounding box also covers the gaps between outputs, keep off those
+                Some(self.clamp_coords(position_transformed(layout.size) + layout.loc.to_f64()))
+            }
+        }
     }
 
     pub fn process_input_event<B: InputBackend>(
@@ -352,21 +554,28 @@ impl BuedchenState<UdevData> {
         dh: &DisplayHandle,
         event: InputEvent<B>,
     ) {
-        match event {
-            InputEvent::Keyboard { event, .. } => match self.keyboard_key_to_action::<B>(event) {
-                KeyAction::VtSwitch(vt) => {
-                    info!(to = vt, "Trying to switch vt");
-                    if let Err(err) = self.backend_data.session.change_vt(vt) {
-                        error!(vt, "Error switching vt: {}", err);
+        if self.swallow_wake_input(&event) {
+            return;
+        }
+
+        // outside of the schedule only key bindings and the lockdown corners reach the compositor
+        if self.schedule.is_off() && is_user_input(&event) {
+            match event {
+                InputEvent::Keyboard { event, .. } => self.on_keyboard_key::<B>(event),
+                InputEvent::TouchDown { event } => {
+                    if let Some(position) = self.transform_position(&event.device(), |size| {
+                        event.position_transformed(size)
+                    }) {
+                        self.lockdown_touch_down(position);
                     }
                 }
-                KeyAction::None => (),
+                _ => {}
+            }
+            return;
+        }
 
-                KeyAction::Quit => {
-                    info!("Quitting.");
-                    self.running.store(false, Ordering::SeqCst);
-                }
-            },
+        match event {
+            InputEvent::Keyboard { event, .. } => self.on_keyboard_key::<B>(event),
             InputEvent::PointerMotion { event, .. } => self.on_pointer_move::<B>(dh, event),
             InputEvent::PointerMotionAbsolute { event, .. } => {
                 self.on_pointer_move_absolute::<B>(dh, event)
@@ -421,10 +630,29 @@ impl BuedchenState<UdevData> {
         }
     }
 
+    fn on_keyboard_key<B: InputBackend>(&mut self, evt: B::KeyboardKeyEvent) {
+        match self.keyboard_key_to_action::<B>(evt) {
+            KeyAction::VtSwitch(vt) => {
+                info!(to = vt, "Trying to switch vt");
+                if let Err(err) = self.backend_data.session.change_vt(vt) {
+                    error!(vt, "Error switching vt: {}", err);
+                }
+            }
+            KeyAction::None => (),
+
+            KeyAction::Run(action) => self.run_action(action),
+        }
+    }
+
     fn on_touch_down<B: InputBackend>(&mut self, evt: B::TouchDownEvent) {
         let serial = SCOUNTER.next_serial();
-        let position = self.transform_position(&evt);
+        let Some(position) =
+            self.transform_position(&evt.device(), |size| evt.position_transformed(size))
+        else {
+            return;
+        };
 
+        self.lockdown_touch_down(position);
         let under = self.surface_under(position);
 
         if let Some((surface, _surface_loc)) = under
@@ -459,7 +687,11 @@ impl BuedchenState<UdevData> {
     }
 
     fn on_touch_motion<B: InputBackend>(&mut self, evt: B::TouchMotionEvent) {
-        let position = self.transform_position(&evt);
+        let Some(position) =
+            self.transform_position(&evt.device(), |size| evt.position_transformed(size))
+        else {
+            return;
+        };
         info!("touch motion at {:?}", position);
 
         self.touch.motion(evt.time_msec(), evt.slot(), position);
@@ -585,22 +817,11 @@ impl BuedchenState<UdevData> {
     ) {
         let serial = SCOUNTER.next_serial();
 
-        let max_x = self.space.outputs().fold(0, |acc, o| {
-            acc + self.space.output_geometry(o).unwrap().size.w
-        });
-
-        let max_h_output = self
-            .space
-            .outputs()
-            .max_by_key(|o| self.space.output_geometry(o).unwrap().size.h)
-            .unwrap();
-
-        let max_y = self.space.output_geometry(max_h_output).unwrap().size.h;
-
-        let mut pointer_location = (evt.x_transformed(max_x), evt.y_transformed(max_y)).into();
-
-        // clamp to screen limits
-        pointer_location = self.clamp_coords(pointer_location);
+        let Some(pointer_location) =
+            self.transform_position(&evt.device(), |size| evt.position_transformed(size))
+        else {
+            return;
+        };
 
         let pointer = self.pointer.clone();
         let under = self.surface_under(pointer_location);
@@ -620,15 +841,9 @@ impl BuedchenState<UdevData> {
     fn on_tablet_tool_axis<B: InputBackend>(&mut self, evt: B::TabletToolAxisEvent) {
         let tablet_seat = self.seat.tablet_seat();
 
-        let output_geometry = self
-            .space
-            .outputs()
-            .next()
-            .map(|o| self.space.output_geometry(o).unwrap());
-
-        if let Some(rect) = output_geometry {
-            let pointer_location = evt.position_transformed(rect.size) + rect.loc.to_f64();
-
+        if let Some(pointer_location) =
+            self.transform_position(&evt.device(), |size| evt.position_transformed(size))
+        {
             let pointer = self.pointer.clone();
             let under = self.surface_under(pointer_location);
             let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&evt.device()));
@@ -684,18 +899,12 @@ impl BuedchenState<UdevData> {
     ) {
         let tablet_seat = self.seat.tablet_seat();
 
-        let output_geometry = self
-            .space
-            .outputs()
-            .next()
-            .map(|o| self.space.output_geometry(o).unwrap());
-
-        if let Some(rect) = output_geometry {
+        if let Some(pointer_location) =
+            self.transform_position(&evt.device(), |size| evt.position_transformed(size))
+        {
             let tool = evt.tool();
             tablet_seat.add_tool::<Self>(dh, &tool);
 
-            let pointer_location = evt.position_transformed(rect.size) + rect.loc.to_f64();
-
             let pointer = self.pointer.clone();
             let under = self.surface_under(pointer_location);
             let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&evt.device()));
@@ -896,21 +1105,27 @@ impl BuedchenState<UdevData> {
 /// Possible results of a keyboard action
 #[derive(Debug)]
 enum KeyAction {
-    /// Quit the compositor
-    Quit,
+    /// Run the action of a binding
+    Run(Action),
     /// Trigger a vt-switch
     VtSwitch(i32),
     /// Do nothing more
     None,
 }
 
-fn process_keyboard_shortcut(modifiers: ModifiersState, keysym: Keysym) -> Option<KeyAction> {
-    if modifiers.ctrl && modifiers.alt && keysym == Keysym::BackSpace
-        || modifiers.logo && keysym == Keysym::q
-    {
-        // ctrl+alt+backspace = quit
-        // logo + q = quit
-        Some(KeyAction::Quit)
+fn process_keyboard_shortcut(
+    shortcuts: &ShortcutConfig,
+    modifiers: ModifiersState,
+    keysym: Keysym,
+    keycode: u32,
+) -> Option<KeyAction> {
+    let matches = |combo: &KeyCombo| combo.matches(&modifiers, keysym, keycode);
+    if shortcuts.quit.iter().any(matches) {
+        Some(KeyAction::Run(Action::Quit))
+    } else if shortcuts.screenshot.iter().any(matches) {
+        Some(KeyAction::Run(Action::Screenshot))
+    } else if let Some(binding) = shortcuts.bindings.iter().find(|b| matches(&b.keys)) {
+        Some(KeyAction::Run(binding.action.clone()))
     } else if (xkb::KEY_XF86Switch_VT_1..=xkb::KEY_XF86Switch_VT_12).contains(&keysym.raw()) {
         // VTSwitch
         Some(KeyAction::VtSwitch(
@@ -0,0 +1,693 @@
+//! Control socket speaking line-delimited JSON
+//!
+//! Every line a client writes is parsed as a [`Request`] and answered with exactly one
+//! [`Response`] line, in order except for screenshots, which are answered once the file is
+//! written. After a `subscribe` request has been acknowledged the connection additionally
+//! receives an [`Event`] line for everything that happens in the compositor.
+//!
+//! The socket lives next to the wayland socket as `$XDG_RUNTIME_DIR/buedchen-<display>.sock`,
+//! its path is exported to the clients as `BUEDCHEN_SOCKET`.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use smithay::{
+    output::Output,
+    reexports::calloop::{
+        channel, generic::Generic, Interest, LoopHandle, Mode, PostAction, RegistrationToken,
+    },
+    wayland::{compositor::with_states, shell::xdg::XdgToplevelSurfaceData},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{transform_name, ColorAdjustment},
+    focus::FocusTarget,
+    shell::WindowElement,
+    state::{Backend, BuedchenState, CalloopData},
+};
+
+/// Environment variable pointing clients and `buedchenctl` to the control socket
+pub const SOCKET_ENV: &str = "BUEDCHEN_SOCKET";
+
+/// Requests longer than this close the connection
+const MAX_REQUEST_LENGTH: usize = 64 * 1024;
+
+/// A client not reading its responses or events is dropped once this much is left unwritten
+const MAX_OUTGOING_LENGTH: usize = 1024 * 1024;
+
+/// Path of the control socket belonging to the given wayland socket
+pub fn socket_path(wayland_socket: &str) -> Option<PathBuf> {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")?;
+    Some(Path::new(&runtime_dir).join(format!("buedchen-{}.sock", wayland_socket)))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Request {
+    /// List the outputs that are part of the layout
+    Outputs,
+    /// List all mapped toplevel windows
+    Toplevels,
+    /// Stop the command line client, or the application on `output`, and start it again
+    RestartClient {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<String>,
+    },
+    /// Write the content of an output, or the first one, as PNG to `path`
+    ///
+    /// The answer comes once the file is written, possibly after the answers to later requests.
+    Screenshot {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        output: Option<String>,
+        path: PathBuf,
+    },
+    /// Turn the display of an output on or off
+    SetOutputPower { output: String, on: bool },
+    /// Change the colour adjustment of an output, values that are not given are kept
+    SetOutputColor {
+        output: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        gamma: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        brightness: Option<f32>,
+        /// Colour temperature of white in Kelvin
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        temperature: Option<u32>,
+    },
+    /// Switch to one of the configured keyboard layouts, given by name like `de` or `ch(fr)`
+    SetKeyboardLayout { layout: String },
+    /// Keep all outputs on or off for `duration` seconds regardless of the schedule
+    OverrideSchedule { on: bool, duration: u64 },
+    /// Follow the schedule again after an override
+    ClearScheduleOverride,
+    /// Read the configuration file again, like on SIGHUP
+    ///
+    /// The answer lists the changed sections and those of them that are only applied by a
+    /// restart or failed to apply.
+    Reload,
+    /// Stop the compositor
+    Quit,
+    /// Receive an [`Event`] line for everything happening from now on
+    Subscribe,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "kebab-case")]
+pub enum Response {
+    Ok,
+    Outputs {
+        outputs: Vec<OutputInfo>,
+    },
+    Toplevels {
+        toplevels: Vec<ToplevelInfo>,
+    },
+    Reloaded {
+        changed: Vec<String>,
+        not_applied: Vec<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl Response {
+    fn error(message: impl Into<String>) -> Response {
+        Response::Error {
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A supervised client process exited, `output` is set for applications from the configuration
+    ClientExited {
+        pid: Option<u32>,
+        output: Option<String>,
+    },
+    OutputAdded {
+        name: String,
+    },
+    OutputRemoved {
+        name: String,
+    },
+    /// The keyboard focus moved to another toplevel, or to none
+    FocusChanged {
+        toplevel: Option<ToplevelInfo>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputInfo {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    /// Current mode, refresh rate in mHz
+    pub mode: Option<ModeInfo>,
+    pub position: (i32, i32),
+    pub scale: f64,
+    pub transform: String,
+    pub powered: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModeInfo {
+    pub width: i32,
+    pub height: i32,
+    pub refresh: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToplevelInfo {
+    pub app_id: Option<String>,
+    pub title: Option<String>,
+    pub pid: Option<u32>,
+    pub output: Option<String>,
+    pub focused: bool,
+}
+
+/// The listening control socket and its open connections
+#[derive(Debug)]
+pub struct IpcServer {
+    path: PathBuf,
+    connections: HashMap<u64, Connection>,
+    next_connection: u64,
+}
+
+#[derive(Debug)]
+struct Connection {
+    stream: UnixStream,
+    /// Lines not written yet as the socket was full
+    outgoing: Vec<u8>,
+    /// Writes `outgoing` once the socket is writable, disabled while there is nothing to write
+    writer: RegistrationToken,
+    subscribed: bool,
+    /// Shut down, the connection is removed once its reader sees the end of the stream
+    closed: bool,
+}
+
+impl Connection {
+    /// Queue a line, closing the connection if it falls too far behind
+    fn send<D>(&mut self, handle: &LoopHandle<'static, D>, line: &[u8]) {
+        if self.closed {
+            return;
+        }
+        if self.outgoing.len() + line.len() > MAX_OUTGOING_LENGTH {
+            debug!("Closing control connection not reading its output");
+            self.close();
+            return;
+        }
+
+        if self.outgoing.is_empty() {
+            if let Err(err) = handle.enable(&self.writer) {
+                warn!(?err, "Failed to write to control connection");
+                self.close();
+                return;
+            }
+        }
+        self.outgoing.extend_from_slice(line);
+    }
+
+    /// Write as much as the socket takes, the writer stays enabled while something is left
+    fn flush(&mut self) -> PostAction {
+        while !self.outgoing.is_empty() {
+            match (&self.stream).write(&self.outgoing) {
+                Ok(0) => self.close(),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return PostAction::Continue,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    debug!(?err, "Failed to write to control connection");
+                    self.close();
+                }
+            }
+        }
+        PostAction::Disable
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.outgoing.clear();
+        // wakes up the reader, which removes the connection
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+impl IpcServer {
+    /// Listen on `path` and serve connections from the event loop
+    pub fn bind<BackendData: Backend + 'static>(
+        handle: &LoopHandle<'static, CalloopData<BackendData>>,
+        path: PathBuf,
+    ) -> Result<IpcServer, Box<dyn std::error::Error>> {
+        // a previous instance on the same display might have left its socket behind
+        if UnixStream::connect(&path).is_err() {
+            let _ = fs::remove_file(&path);
+        }
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        handle
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                move |_, listener, data| {
+                    loop {
+                        match listener.accept() {
+                            Ok((stream, _)) => data.state.serve_ipc_connection(stream),
+                            Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                            Err(err) => {
+                                warn!(?err, "Failed to accept control connection");
+                                break;
+                            }
+                        }
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|err| err.error)?;
+
+        Ok(IpcServer {
+            path,
+            connections: HashMap::new(),
+            next_connection: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn send<D, T: Serialize>(&mut self, handle: &LoopHandle<'static, D>, id: u64, value: &T) {
+        if let (Some(connection), Some(line)) = (self.connections.get_mut(&id), to_line(value)) {
+            connection.send(handle, &line);
+        }
+    }
+
+    fn broadcast<D>(&mut self, handle: &LoopHandle<'static, D>, event: &Event) {
+        let mut subscribers = self
+            .connections
+            .values_mut()
+            .filter(|connection| connection.subscribed)
+            .peekable();
+        if subscribers.peek().is_none() {
+            return;
+        }
+        let Some(line) = to_line(event) else {
+            return;
+        };
+        for connection in subscribers {
+            connection.send(handle, &line);
+        }
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn to_line<T: Serialize>(value: &T) -> Option<Vec<u8>> {
+    match serde_json::to_vec(value) {
+        Ok(mut line) => {
+            line.push(b'\n');
+            Some(line)
+        }
+        Err(err) => {
+            warn!(?err, "Failed to serialize control message");
+            None
+        }
+    }
+}
+
+impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
+    /// Send an event to all subscribed control connections
+    pub fn emit_ipc_event(&mut self, event: Event) {
+        if let Some(ipc) = self.ipc.as_mut() {
+            ipc.broadcast(&self.handle, &event);
+        }
+    }
+
+    fn serve_ipc_connection(&mut self, stream: UnixStream) {
+        let Some(ipc) = self.ipc.as_mut() else {
+            return;
+        };
+        let id = ipc.next_connection;
+        ipc.next_connection += 1;
+
+        let streams = stream
+            .set_nonblocking(true)
+            .and_then(|_| Ok((stream.try_clone()?, stream.try_clone()?)));
+        let (reader, writer) = match streams {
+            Ok(streams) => streams,
+            Err(err) => {
+                warn!(?err, "Failed to set up control connection");
+                return;
+            }
+        };
+
+        let writer = match self.handle.insert_source(
+            Generic::new(writer, Interest::WRITE, Mode::Level),
+            move |_, _, data| {
+                Ok(data
+                    .state
+                    .ipc
+                    .as_mut()
+                    .and_then(|ipc| ipc.connections.get_mut(&id))
+                    .map_or(PostAction::Disable, Connection::flush))
+            },
+        ) {
+            Ok(token) => token,
+            Err(err) => {
+                warn!(?err, "Failed to serve control connection");
+                return;
+            }
+        };
+        // only polled while there is something to write
+        if let Err(err) = self.handle.disable(&writer) {
+            warn!(?err, "Failed to serve control connection");
+            self.handle.remove(writer);
+            return;
+        }
+
+        let mut buffer = Vec::new();
+        let mut chunk = vec![0u8; 4096];
+        let res = self.handle.insert_source(
+            Generic::new(reader, Interest::READ, Mode::Level),
+            move |_, reader, data| {
+                // Safety: we don't drop the stream
+                let read = match unsafe { reader.get_mut() }.read(&mut chunk) {
+                    Ok(read) => read,
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+                        ) =>
+                    {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(err) => {
+                        debug!(?err, "Failed to read from control connection");
+                        0
+                    }
+                };
+                let closed = data
+                    .state
+                    .ipc
+                    .as_ref()
+                    .and_then(|ipc| ipc.connections.get(&id))
+                    .map_or(true, |connection| connection.closed);
+                if read == 0 || closed {
+                    if let Some(ipc) = data.state.ipc.as_mut() {
+                        if let Some(connection) = ipc.connections.remove(&id) {
+                            data.state.handle.remove(connection.writer);
+                        }
+                    }
+                    return Ok(PostAction::Remove);
+                }
+
+                buffer.extend_from_slice(&chunk[..read]);
+                while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    if line.iter().all(u8::is_ascii_whitespace) {
+                        continue;
+                    }
+
+                    let response = match serde_json::from_slice::<Request>(&line) {
+                        Ok(Request::Subscribe) => {
+                            if let Some(connection) = data
+                                .state
+                                .ipc
+                                .as_mut()
+                                .and_then(|ipc| ipc.connections.get_mut(&id))
+                            {
+                                connection.subscribed = true;
+                            }
+                            Response::Ok
+                        }
+                        Ok(Request::Screenshot { output, path }) => {
+                            data.state.screenshot_for_ipc(id, output, path);
+                            continue;
+                        }
+                        Ok(request) => data.state.handle_ipc_request(request),
+                        Err(err) => Response::error(format!("Invalid request: {}", err)),
+                    };
+                    data.state.send_ipc(id, &response);
+                }
+
+                if buffer.len() > MAX_REQUEST_LENGTH {
+                    data.state
+                        .send_ipc(id, &Response::error("Request too long"));
+                    if let Some(connection) = data
+                        .state
+                        .ipc
+                        .as_mut()
+                        .and_then(|ipc| ipc.connections.get_mut(&id))
+                    {
+                        // the error is only sent if it fits into the socket right away
+                        connection.flush();
+                        connection.close();
+                    }
+                }
+                Ok(PostAction::Continue)
+            },
+        );
+        let Some(ipc) = self.ipc.as_mut() else {
+            return;
+        };
+        match res {
+            Ok(_) => {
+                ipc.connections.insert(
+                    id,
+                    Connection {
+                        stream,
+                        outgoing: Vec::new(),
+                        writer,
+                        subscribed: false,
+                        closed: false,
+                    },
+                );
+            }
+            Err(err) => {
+                warn!(?err, "Failed to serve control connection");
+                self.handle.remove(writer);
+            }
+        }
+    }
+
+    fn send_ipc<T: Serialize>(&mut self, id: u64, value: &T) {
+        if let Some(ipc) = self.ipc.as_mut() {
+            ipc.send(&self.handle, id, value);
+        }
+    }
+
+    /// Take a screenshot and answer connection `id` once it is written, or failed to
+    fn screenshot_for_ipc(&mut self, id: u64, output: Option<String>, path: PathBuf) {
+        debug!(?output, ?path, "Control request for a screenshot");
+        let (sender, written) = channel::channel::<Result<(), String>>();
+        // the channel removes itself once the writing thread dropped the sender
+        let res = self.handle.insert_source(written, move |event, _, data| {
+            if let channel::Event::Msg(res) = event {
+                let response = match res {
+                    Ok(()) => Response::Ok,
+                    Err(err) => Response::error(err),
+                };
+                data.state.send_ipc(id, &response);
+            }
+        });
+        if let Err(err) = res {
+            warn!(?err, "Failed to wait for the screenshot");
+            self.send_ipc(id, &Response::error("Failed to wait for the screenshot"));
+            return;
+        }
+
+        let done = move |res| {
+            let _ = sender.send(res);
+        };
+        if let Err(err) = self.screenshot(output.as_deref(), path, done) {
+            self.send_ipc(id, &Response::error(err));
+        }
+    }
+
+    fn handle_ipc_request(&mut self, request: Request) -> Response {
+        debug!(?request, "Control request");
+        match request {
+            Request::Outputs => Response::Outputs {
+                outputs: self
+                    .space
+                    .outputs()
+                    .map(|output| self.output_info(output))
+                    .collect(),
+            },
+            Request::Toplevels => {
+                let focus = self
+                    .seat
+                    .get_keyboard()
+                    .and_then(|keyboard| keyboard.current_focus());
+                Response::Toplevels {
+                    toplevels: self
+                        .space
+                        .elements()
+                        .map(|window| {
+                            let focused = focus == Some(FocusTarget::Window(window.clone()));
+                            self.toplevel_info(window, focused)
+                        })
+                        .collect(),
+                }
+            }
+            Request::RestartClient { output } => {
+                match self
+                    .client_slot(output.as_deref())
+                    .and_then(|slot| self.restart_client(slot))
+                {
+                    Ok(()) => Response::Ok,
+                    Err(err) => Response::error(err),
+                }
+            }
+            Request::Screenshot { .. } => {
+                unreachable!("screenshots are answered once the file is written")
+            }
+            Request::SetOutputPower { output, on } => {
+                let Some(output) = self.space.outputs().find(|o| o.name() == output).cloned()
+                else {
+                    return Response::error(format!("No output named {}", output));
+                };
+                if self.backend_data.set_output_power(&output, on) {
+                    Response::Ok
+                } else {
+                    Response::error("The backend can't change the output power")
+                }
+            }
+            Request::SetOutputColor {
+                output,
+                gamma,
+                brightness,
+                temperature,
+            } => {
+                let Some(output) = self.space.outputs().find(|o| o.name() == output).cloned()
+                else {
+                    return Response::error(format!("No output named {}", output));
+                };
+                let Some(current) = self.backend_data.color_adjustment(&output) else {
+                    return Response::error("The backend can't adjust the output colours");
+                };
+                let adjustment = ColorAdjustment {
+                    gamma: gamma.unwrap_or(current.gamma),
+                    brightness: brightness.unwrap_or(current.brightness),
+                    temperature: temperature.unwrap_or(current.temperature),
+                };
+                if let Err(err) = adjustment.validate() {
+                    return Response::error(err);
+                }
+                if self.backend_data.set_color_adjustment(&output, adjustment) {
+                    Response::Ok
+                } else {
+                    Response::error("Failed to set the gamma ramps of the output")
+                }
+            }
+            Request::OverrideSchedule { on, duration } => {
+                match self.override_schedule(on, Duration::from_secs(duration)) {
+                    Ok(()) => Response::Ok,
+                    Err(err) => Response::error(err),
+                }
+            }
+            Request::SetKeyboardLayout { layout } => {
+                let Some(index) = self.config.keyboard.find_layout(&layout) else {
+                    return Response::error(format!("No keyboard layout named {}", layout));
+                };
+                match self.set_keyboard_layout(index) {
+                    Ok(()) => Response::Ok,
+                    Err(err) => Response::error(err),
+                }
+            }
+            Request::ClearScheduleOverride => {
+                self.clear_schedule_override();
+                Response::Ok
+            }
+            Request::Reload => {
+                info!("Reloading configuration on request");
+                match self.reload() {
+                    Ok(report) => Response::Reloaded {
+                        changed: report.changed.into_iter().map(String::from).collect(),
+                        not_applied: report.not_applied.into_iter().map(String::from).collect(),
+                    },
+                    Err(err) => Response::error(err),
+                }
+            }
+            Request::Quit => {
+                info!("Quit requested over the control socket");
+                self.running.store(false, Ordering::SeqCst);
+                Response::Ok
+            }
+            Request::Subscribe => unreachable!("subscriptions are handled by the connection"),
+        }
+    }
+
+    fn output_info(&self, output: &Output) -> OutputInfo {
+        let properties = output.physical_properties();
+        let position = self
+            .space
+            .output_geometry(output)
+            .map_or((0, 0), |geometry| (geometry.loc.x, geometry.loc.y));
+        OutputInfo {
+            name: output.name(),
+            make: properties.make,
+            model: properties.model,
+            mode: output.current_mode().map(|mode| ModeInfo {
+                width: mode.size.w,
+                height: mode.size.h,
+                refresh: mode.refresh,
+            }),
+            position,
+            scale: output.current_scale().fractional_scale(),
+            transform: transform_name(output.current_transform()).to_string(),
+            powered: self.backend_data.is_output_powered(output),
+        }
+    }
+
+    fn toplevel_info(&self, window: &WindowElement, focused: bool) -> ToplevelInfo {
+        let surface = window.0.toplevel().wl_surface();
+        let (app_id, title) = with_states(surface, |states| {
+            let data = states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap();
+            (data.app_id.clone(), data.title.clone())
+        });
+        ToplevelInfo {
+            app_id,
+            title,
+            pid: self.client_pid(surface),
+            output: self
+                .space
+                .outputs_for_element(window)
+                .first()
+                .map(|output| output.name()),
+            focused,
+        }
+    }
+
+    /// Report a keyboard focus change to subscribers, popups and layer surfaces are not reported
+    pub fn emit_focus_changed(&mut self, target: Option<&FocusTarget>) {
+        let toplevel = match target {
+            Some(FocusTarget::Window(window)) => Some(self.toplevel_info(window, true)),
+            None => None,
+            Some(_) => return,
+        };
+        self.emit_ipc_event(Event::FocusChanged { toplevel });
+    }
+}
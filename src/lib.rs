@@ -1,11 +1,25 @@
 pub mod client;
+pub mod config;
 pub mod cursor;
 pub mod drawing;
 pub mod focus;
+pub mod gamma_control;
+pub mod headless;
+pub mod idle;
+pub mod idle_inhibit;
+pub mod idle_notify;
 pub mod input_handler;
+pub mod ipc;
+pub mod lockdown;
+pub mod output_management;
+pub mod output_power;
 pub mod render;
+pub mod schedule;
+pub mod screencopy;
+pub mod screenshot;
 pub mod shell;
 pub mod state;
 pub mod udev;
+pub mod winit;
 
 pub use state::{BuedchenState, CalloopData, ClientState};
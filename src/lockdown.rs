@@ -0,0 +1,169 @@
+//! Locking the compositor shortcuts on public kiosks
+//!
+//! While locked, shortcuts are handed to the clients like any other key. An admin unlocks them
+//! for a while by holding a key chord, typing a sequence or touching the output corners in a
+//! given order. The input used for unlocking is still handed to the clients.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use smithay::{
+    input::keyboard::{xkb, Keysym, ModifiersState},
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        RegistrationToken,
+    },
+    utils::{Logical, Point},
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::Corner,
+    state::{Backend, BuedchenState},
+};
+
+#[derive(Debug, Default)]
+pub struct LockdownState {
+    unlocked_until: Option<Instant>,
+    /// Key of the held chord and the timer unlocking once it was held long enough
+    chord: Option<(u32, RegistrationToken)>,
+    typed: VecDeque<(Instant, char)>,
+    touched: VecDeque<(Instant, Corner)>,
+}
+
+/// Drop the entries older than `window` and keep at most `len` of them
+fn trim<T>(entries: &mut VecDeque<(Instant, T)>, window: Duration, len: usize) {
+    while entries.len() > len
+        || entries
+            .front()
+            .map_or(false, |(time, _)| time.elapsed() > window)
+    {
+        entries.pop_front();
+    }
+}
+
+impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
+    /// Whether the compositor shortcuts are currently ignored
+    pub fn shortcuts_locked(&self) -> bool {
+        self.config.lockdown.enabled
+            && self
+                .lockdown
+                .unlocked_until
+                .map_or(true, |until| Instant::now() >= until)
+    }
+
+    fn unlock(&mut self, method: &str) {
+        let duration = Duration::from_secs(self.config.lockdown.unlock_duration);
+        info!(method, ?duration, "Shortcuts unlocked");
+        self.lockdown.unlocked_until = Some(Instant::now() + duration);
+        self.lockdown.typed.clear();
+        self.lockdown.touched.clear();
+    }
+
+    /// Track a key press for the chord and the typed sequence
+    pub fn lockdown_key_pressed(
+        &mut self,
+        modifiers: &ModifiersState,
+        keysym: Keysym,
+        keycode: u32,
+    ) {
+        if !self.config.lockdown.enabled {
+            return;
+        }
+
+        if let Some(sequence) = self.config.lockdown.sequence.clone() {
+            if let Some(c) = char::from_u32(xkb::keysym_to_utf32(keysym)).filter(|c| *c != '\0') {
+                let window = Duration::from_secs(self.config.lockdown.window);
+                let typed = &mut self.lockdown.typed;
+                typed.push_back((Instant::now(), c));
+                trim(typed, window, sequence.chars().count());
+                if typed.iter().map(|(_, c)| c).eq(sequence.chars()) {
+                    self.unlock("sequence");
+                }
+            }
+        }
+
+        let Some(chord) = self.config.lockdown.chord else {
+            return;
+        };
+        if !chord.matches(modifiers, keysym, keycode) || self.lockdown.chord.is_some() {
+            return;
+        }
+        let hold = Duration::from_secs(self.config.lockdown.chord_hold);
+        let res = self
+            .handle
+            .insert_source(Timer::from_duration(hold), move |_, _, data| {
+                let state = &mut data.state;
+                state.lockdown.chord = None;
+                // the key is still held, but the modifiers might have been let go in the meantime
+                let modifiers = state.seat.get_keyboard().unwrap().modifier_state();
+                if chord.matches(&modifiers, keysym, keycode) {
+                    state.unlock("chord");
+                }
+                TimeoutAction::Drop
+            });
+        match res {
+            Ok(token) => self.lockdown.chord = Some((keycode, token)),
+            Err(err) => warn!("Failed to start the unlock chord timer: {}", err),
+        }
+    }
+
+    /// Stop waiting for a chord whose key was released
+    pub fn lockdown_key_released(&mut self, keycode: u32) {
+        if let Some((held, token)) = self.lockdown.chord {
+            if held == keycode {
+                self.handle.remove(token);
+                self.lockdown.chord = None;
+            }
+        }
+    }
+
+    /// Track a touch for the corner gesture, `position` is in global coordinates
+    pub fn lockdown_touch_down(&mut self, position: Point<f64, Logical>) {
+        let config = &self.config.lockdown;
+        if !config.enabled || config.corners.is_empty() {
+            return;
+        }
+
+        let corner = self
+            .space
+            .output_under(position)
+            .next()
+            .and_then(|output| self.space.output_geometry(output))
+            .and_then(|geometry| {
+                let size = config.corner_size as f64;
+                let local = position - geometry.loc.to_f64();
+                let left = local.x < size;
+                let right = local.x >= geometry.size.w as f64 - size;
+                let top = local.y < size;
+                let bottom = local.y >= geometry.size.h as f64 - size;
+                match (left, right, top, bottom) {
+                    (true, _, true, _) => Some(Corner::TopLeft),
+                    (_, true, true, _) => Some(Corner::TopRight),
+                    (true, _, _, true) => Some(Corner::BottomLeft),
+                    (_, true, _, true) => Some(Corner::BottomRight),
+                    _ => None,
+                }
+            });
+        // touching anywhere else starts over
+        let Some(corner) = corner else {
+            self.lockdown.touched.clear();
+            return;
+        };
+
+        let window = Duration::from_secs(config.window);
+        let len = config.corners.len();
+        let touched = &mut self.lockdown.touched;
+        touched.push_back((Instant::now(), corner));
+        trim(touched, window, len);
+        if touched
+            .iter()
+            .map(|(_, corner)| corner)
+            .eq(self.config.lockdown.corners.iter())
+        {
+            self.unlock("corners");
+        }
+    }
+}
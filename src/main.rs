@@ -1,16 +1,96 @@
+use std::{path::PathBuf, time::Duration};
+
+use buedchen::{
+    client::{ClientLog, ClientSupervisor, GiveUpAction, RestartMode, RestartPolicy},
+    config::Config,
+    headless::HeadlessOutputConfig,
+};
 use clap::Parser;
+use nix::sys::signal::{SigSet, Signal};
 
 /// A wayland compositor that implements a full screen kiosk shell
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    #[arg(last(true), required(true))]
+    /// Start on a tty using udev (default)
+    #[arg(long, group = "backend")]
+    tty_udev: bool,
+    /// Start as a window inside of another X11 or Wayland session
+    #[arg(long, group = "backend")]
+    winit: bool,
+    /// Start without a real display or input devices, rendering in software
+    #[arg(long, group = "backend")]
+    headless: bool,
+    /// Virtual output of the headless backend as WIDTHxHEIGHT[@REFRESH], can be repeated
+    #[arg(long = "headless-output", default_value = "1920x1080@60")]
+    headless_outputs: Vec<HeadlessOutputConfig>,
+    /// Configuration file, defaults to /etc/buedchen/config.toml if it exists
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// When to restart the client after it exited
+    #[arg(long, value_enum, default_value_t = RestartMode::Never)]
+    restart: RestartMode,
+    /// Initial delay in seconds before restarting the client, doubled on every restart
+    #[arg(long, default_value_t = 1)]
+    restart_delay: u64,
+    /// Maximum delay in seconds between two restarts
+    #[arg(long, default_value_t = 60)]
+    restart_max_delay: u64,
+    /// Maximum number of restarts within the restart window before giving up
+    #[arg(long, default_value_t = 5)]
+    max_restarts: u32,
+    /// Length of the restart window in seconds
+    #[arg(long, default_value_t = 60)]
+    restart_window: u64,
+    /// What to do once the client is not restarted anymore
+    #[arg(long, value_enum, default_value_t = GiveUpAction::Exit)]
+    on_give_up: GiveUpAction,
+    /// Additionally write the output of the client into this file
+    #[arg(long)]
+    client_log: Option<PathBuf>,
+    /// Size in bytes after which the client log file is rotated
+    #[arg(long, default_value_t = 10 * 1024 * 1024)]
+    client_log_size: u64,
+    /// Number of rotated client log files to keep
+    #[arg(long, default_value_t = 3)]
+    client_log_files: usize,
+    /// Kiosk client and its arguments, optional if applications are configured
+    #[arg(last(true))]
     executable: Vec<String>,
 }
 
-fn run_udev(executable: &[String]) {
+impl Cli {
+    fn restart_policy(&self) -> RestartPolicy {
+        RestartPolicy {
+            mode: self.restart,
+            initial_backoff: Duration::from_secs(self.restart_delay),
+            max_backoff: Duration::from_secs(self.restart_max_delay),
+            max_restarts: self.max_restarts,
+            window: Duration::from_secs(self.restart_window),
+            give_up: self.on_give_up,
+        }
+    }
+
+    fn client_log(&self) -> Option<ClientLog> {
+        self.client_log
+            .clone()
+            .map(|path| ClientLog::new(path, self.client_log_size, self.client_log_files))
+    }
+}
+
+fn run_udev(client: ClientSupervisor, config: Config) {
     tracing::info!("Starting buedchen on a tty using udev");
-    buedchen::udev::run_udev(executable);
+    buedchen::udev::run_udev(client, config);
+}
+
+fn run_winit(client: ClientSupervisor, config: Config) {
+    tracing::info!("Starting buedchen with winit backend");
+    buedchen::winit::run_winit(client, config);
+}
+
+fn run_headless(client: ClientSupervisor, config: Config, outputs: &[HeadlessOutputConfig]) {
+    tracing::info!("Starting buedchen headless");
+    buedchen::headless::run_headless(client, config, outputs);
 }
 
 fn main() {
@@ -25,6 +105,38 @@ fn main() {
         tracing_subscriber::fmt().compact().init();
     }
 
+    // The event loop receives these through a signalfd, which requires them to be blocked in
+    // every thread. Threads inherit the mask, so block them before the backends spawn any, e.g.
+    // inside of the GL driver.
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGHUP);
+    signals.add(Signal::SIGUSR1);
+    if let Err(err) = signals.thread_block() {
+        tracing::warn!("Failed to block SIGHUP and SIGUSR1: {}", err);
+    }
+
     let cli = Cli::parse();
-    run_udev(&cli.executable);
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if cli.executable.is_empty() && config.apps.is_empty() {
+        tracing::error!("No client given, pass one after `--` or configure [[apps]]");
+        std::process::exit(1);
+    }
+    let client = ClientSupervisor::new(
+        cli.executable.clone(),
+        cli.restart_policy(),
+        cli.client_log(),
+    );
+    if cli.winit {
+        run_winit(client, config);
+    } else if cli.headless {
+        run_headless(client, config, &cli.headless_outputs);
+    } else {
+        run_udev(client, config);
+    }
 }
@@ -0,0 +1,604 @@
+//! Server side of the `wlr-output-management-unstable-v1` protocol
+//!
+//! The protocol state only keeps track of the heads advertised to clients,
+//! applying a configuration is left to the backend through [`OutputManagementHandler`].
+
+use std::sync::Mutex;
+
+use smithay::{
+    reexports::{
+        wayland_protocols_wlr::output_management::v1::server::{
+            zwlr_output_configuration_head_v1::{self, ZwlrOutputConfigurationHeadV1},
+            zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+            zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
+            zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+            zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            protocol::wl_output,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+        },
+    },
+    utils::{Logical, Physical, Point, Size, Transform},
+};
+use tracing::warn;
+
+const VERSION: u32 = 2;
+
+/// A mode supported by a head
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputHeadMode {
+    pub size: Size<i32, Physical>,
+    /// Refresh rate in mHz
+    pub refresh: i32,
+    pub preferred: bool,
+}
+
+/// Everything advertised about a single output, whether it is enabled or not
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputHead {
+    pub name: String,
+    pub description: String,
+    pub make: String,
+    pub model: String,
+    pub serial: Option<String>,
+    /// Physical size in millimeters
+    pub physical_size: (i32, i32),
+    pub modes: Vec<OutputHeadMode>,
+    pub enabled: bool,
+    /// Index into `modes`, only used if the head is enabled
+    pub current_mode: Option<usize>,
+    pub position: Point<i32, Logical>,
+    pub transform: Transform,
+    pub scale: f64,
+}
+
+/// Requested state of a single head, unset values are meant to stay as they are
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadConfiguration {
+    pub name: String,
+    pub enabled: bool,
+    /// Size and refresh rate in mHz of one of the advertised modes
+    pub mode: Option<(Size<i32, Physical>, i32)>,
+    /// Size and refresh rate in mHz of a mode that was not advertised, refresh might be 0
+    pub custom_mode: Option<(Size<i32, Physical>, i32)>,
+    pub position: Option<Point<i32, Logical>>,
+    pub transform: Option<Transform>,
+    pub scale: Option<f64>,
+}
+
+impl HeadConfiguration {
+    fn new(name: String, enabled: bool) -> Self {
+        HeadConfiguration {
+            name,
+            enabled,
+            mode: None,
+            custom_mode: None,
+            position: None,
+            transform: None,
+            scale: None,
+        }
+    }
+}
+
+pub trait OutputManagementHandler {
+    fn output_management_state(&mut self) -> &mut OutputManagementState;
+
+    /// Apply a configuration covering all heads, or only check if it could be applied
+    ///
+    /// Either everything is applied or nothing, returns whether the configuration succeeded.
+    fn apply_output_configuration(
+        &mut self,
+        configuration: Vec<HeadConfiguration>,
+        test_only: bool,
+    ) -> bool;
+}
+
+pub struct OutputManagerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+pub struct ModeData {
+    head: String,
+    mode: OutputHeadMode,
+}
+
+pub struct ConfigurationData {
+    serial: u32,
+    inner: Mutex<ConfigurationInner>,
+}
+
+#[derive(Default)]
+struct ConfigurationInner {
+    used: bool,
+    /// Configured heads by name, `None` for disabled heads
+    heads: Vec<(String, Option<ZwlrOutputConfigurationHeadV1>)>,
+}
+
+struct ManagerInstance {
+    manager: ZwlrOutputManagerV1,
+    heads: Vec<(ZwlrOutputHeadV1, Vec<ZwlrOutputModeV1>)>,
+}
+
+impl ManagerInstance {
+    fn finish_heads(&mut self) {
+        for (head, modes) in self.heads.drain(..) {
+            for mode in modes {
+                mode.finished();
+            }
+            head.finished();
+        }
+    }
+
+    fn send_heads<D>(&mut self, dh: &DisplayHandle, client: &Client, heads: &[OutputHead])
+    where
+        D: Dispatch<ZwlrOutputHeadV1, String> + Dispatch<ZwlrOutputModeV1, ModeData> + 'static,
+    {
+        let version = self.manager.version();
+        for head in heads {
+            let Ok(head_resource) =
+                client.create_resource::<ZwlrOutputHeadV1, _, D>(dh, version, head.name.clone())
+            else {
+                continue;
+            };
+            self.manager.head(&head_resource);
+            head_resource.name(head.name.clone());
+            head_resource.description(head.description.clone());
+            if head.physical_size.0 > 0 && head.physical_size.1 > 0 {
+                head_resource.physical_size(head.physical_size.0, head.physical_size.1);
+            }
+
+            let mut modes = Vec::new();
+            for mode in &head.modes {
+                let data = ModeData {
+                    head: head.name.clone(),
+                    mode: *mode,
+                };
+                let Ok(mode_resource) =
+                    client.create_resource::<ZwlrOutputModeV1, _, D>(dh, version, data)
+                else {
+                    continue;
+                };
+                head_resource.mode(&mode_resource);
+                mode_resource.size(mode.size.w, mode.size.h);
+                if mode.refresh > 0 {
+                    mode_resource.refresh(mode.refresh);
+                }
+                if mode.preferred {
+                    mode_resource.preferred();
+                }
+                modes.push(mode_resource);
+            }
+
+            head_resource.enabled(head.enabled as i32);
+            if head.enabled {
+                if let Some(mode) = head.current_mode.and_then(|index| modes.get(index)) {
+                    head_resource.current_mode(mode);
+                }
+                head_resource.position(head.position.x, head.position.y);
+                head_resource.transform(head.transform.into());
+                head_resource.scale(head.scale);
+            }
+
+            if version >= zwlr_output_head_v1::EVT_MAKE_SINCE {
+                head_resource.make(head.make.clone());
+                head_resource.model(head.model.clone());
+                if let Some(serial) = head.serial.clone() {
+                    head_resource.serial_number(serial);
+                }
+            }
+
+            self.heads.push((head_resource, modes));
+        }
+    }
+}
+
+pub struct OutputManagementState {
+    global: GlobalId,
+    serial: u32,
+    heads: Vec<OutputHead>,
+    managers: Vec<ManagerInstance>,
+}
+
+impl std::fmt::Debug for OutputManagementState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputManagementState")
+            .field("global", &self.global)
+            .field("serial", &self.serial)
+            .field("heads", &self.heads)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OutputManagementState {
+    /// Create the `zwlr_output_manager_v1` global, only visible to clients passing `filter`
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrOutputManagerV1, OutputManagerGlobalData>
+            + Dispatch<ZwlrOutputManagerV1, ()>
+            + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        let global = display.create_global::<D, ZwlrOutputManagerV1, _>(
+            VERSION,
+            OutputManagerGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+
+        OutputManagementState {
+            global,
+            serial: 0,
+            heads: Vec::new(),
+            managers: Vec::new(),
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Advertise the current heads to all clients, if they changed
+    pub fn update<D>(&mut self, dh: &DisplayHandle, heads: Vec<OutputHead>)
+    where
+        D: Dispatch<ZwlrOutputHeadV1, String> + Dispatch<ZwlrOutputModeV1, ModeData> + 'static,
+    {
+        if heads == self.heads {
+            return;
+        }
+        self.heads = heads;
+        self.serial = self.serial.wrapping_add(1);
+
+        for instance in &mut self.managers {
+            let Some(client) = instance.manager.client() else {
+                continue;
+            };
+            // simply announce everything again, clients rebuild their state on `done`
+            instance.finish_heads();
+            instance.send_heads::<D>(dh, &client, &self.heads);
+            instance.manager.done(self.serial);
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrOutputManagerV1, OutputManagerGlobalData, D> for OutputManagementState
+where
+    D: GlobalDispatch<ZwlrOutputManagerV1, OutputManagerGlobalData>
+        + Dispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputHeadV1, String>
+        + Dispatch<ZwlrOutputModeV1, ModeData>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn bind(
+        state: &mut D,
+        handle: &DisplayHandle,
+        client: &Client,
+        resource: New<ZwlrOutputManagerV1>,
+        _global_data: &OutputManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+        let mut instance = ManagerInstance {
+            manager,
+            heads: Vec::new(),
+        };
+
+        let output_management_state = state.output_management_state();
+        instance.send_heads::<D>(handle, client, &output_management_state.heads);
+        instance.manager.done(output_management_state.serial);
+        output_management_state.managers.push(instance);
+    }
+
+    fn can_view(client: Client, global_data: &OutputManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputManagerV1, (), D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputConfigurationV1, ConfigurationData>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputManagerV1,
+        request: zwlr_output_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_manager_v1::Request::CreateConfiguration { id, serial } => {
+                data_init.init(
+                    id,
+                    ConfigurationData {
+                        serial,
+                        inner: Mutex::new(ConfigurationInner::default()),
+                    },
+                );
+            }
+            zwlr_output_manager_v1::Request::Stop => {
+                let managers = &mut state.output_management_state().managers;
+                if let Some(index) = managers.iter().position(|i| &i.manager == resource) {
+                    let mut instance = managers.remove(index);
+                    instance.finish_heads();
+                }
+                resource.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrOutputManagerV1, _data: &()) {
+        state
+            .output_management_state()
+            .managers
+            .retain(|instance| &instance.manager != resource);
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputHeadV1, String, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputHeadV1, String> + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrOutputHeadV1,
+        _request: zwlr_output_head_v1::Request,
+        _data: &String,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        // only `release` from version 3, which is not advertised
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputModeV1, ModeData, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputModeV1, ModeData> + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrOutputModeV1,
+        _request: zwlr_output_mode_v1::Request,
+        _data: &ModeData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        // only `release` from version 3, which is not advertised
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationV1, ConfigurationData, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputConfigurationV1, ConfigurationData>
+        + Dispatch<ZwlrOutputConfigurationHeadV1, Mutex<HeadConfiguration>>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputConfigurationV1,
+        request: zwlr_output_configuration_v1::Request,
+        data: &ConfigurationData,
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let mut inner = data.inner.lock().unwrap();
+        match request {
+            zwlr_output_configuration_v1::Request::EnableHead { id, head } => {
+                let name = head.data::<String>().cloned().unwrap_or_default();
+                let config_head =
+                    data_init.init(id, Mutex::new(HeadConfiguration::new(name.clone(), true)));
+                configure_head(resource, &mut inner, name, Some(config_head));
+            }
+            zwlr_output_configuration_v1::Request::DisableHead { head } => {
+                let name = head.data::<String>().cloned().unwrap_or_default();
+                configure_head(resource, &mut inner, name, None);
+            }
+            request @ (zwlr_output_configuration_v1::Request::Apply
+            | zwlr_output_configuration_v1::Request::Test) => {
+                let test_only = matches!(request, zwlr_output_configuration_v1::Request::Test);
+                if inner.used {
+                    resource.post_error(
+                        zwlr_output_configuration_v1::Error::AlreadyUsed,
+                        "configuration has already been applied or tested",
+                    );
+                    return;
+                }
+                inner.used = true;
+
+                let output_management_state = state.output_management_state();
+                if data.serial != output_management_state.serial {
+                    resource.cancelled();
+                    return;
+                }
+                if let Some(head) = output_management_state.heads.iter().find(|head| {
+                    !inner
+                        .heads
+                        .iter()
+                        .any(|(configured, _)| configured == &head.name)
+                }) {
+                    resource.post_error(
+                        zwlr_output_configuration_v1::Error::UnconfiguredHead,
+                        format!("head {} has not been configured", head.name),
+                    );
+                    return;
+                }
+
+                let configuration = inner
+                    .heads
+                    .iter()
+                    .map(|(name, config_head)| match config_head {
+                        Some(config_head) => config_head
+                            .data::<Mutex<HeadConfiguration>>()
+                            .map(|config| config.lock().unwrap().clone())
+                            .unwrap_or_else(|| HeadConfiguration::new(name.clone(), true)),
+                        None => HeadConfiguration::new(name.clone(), false),
+                    })
+                    .collect::<Vec<_>>();
+                drop(inner);
+
+                if state.apply_output_configuration(configuration, test_only) {
+                    resource.succeeded();
+                } else {
+                    resource.failed();
+                }
+            }
+            zwlr_output_configuration_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn configure_head(
+    resource: &ZwlrOutputConfigurationV1,
+    inner: &mut ConfigurationInner,
+    name: String,
+    config_head: Option<ZwlrOutputConfigurationHeadV1>,
+) {
+    if inner.used {
+        resource.post_error(
+            zwlr_output_configuration_v1::Error::AlreadyUsed,
+            "configuration has already been applied or tested",
+        );
+    } else if inner
+        .heads
+        .iter()
+        .any(|(configured, _)| *configured == name)
+    {
+        resource.post_error(
+            zwlr_output_configuration_v1::Error::AlreadyConfiguredHead,
+            format!("head {} has already been configured", name),
+        );
+    } else {
+        inner.heads.push((name, config_head));
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationHeadV1, Mutex<HeadConfiguration>, D>
+    for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputConfigurationHeadV1, Mutex<HeadConfiguration>> + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputConfigurationHeadV1,
+        request: zwlr_output_configuration_head_v1::Request,
+        data: &Mutex<HeadConfiguration>,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let mut config = data.lock().unwrap();
+        let already_set = |resource: &ZwlrOutputConfigurationHeadV1, what: &str| {
+            resource.post_error(
+                zwlr_output_configuration_head_v1::Error::AlreadySet,
+                format!("{} has already been set", what),
+            );
+        };
+
+        match request {
+            zwlr_output_configuration_head_v1::Request::SetMode { mode } => {
+                if config.mode.is_some() || config.custom_mode.is_some() {
+                    return already_set(resource, "mode");
+                }
+                match mode.data::<ModeData>() {
+                    Some(mode) if mode.head == config.name => {
+                        config.mode = Some((mode.mode.size, mode.mode.refresh));
+                    }
+                    _ => resource.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidMode,
+                        "mode doesn't belong to this head",
+                    ),
+                }
+            }
+            zwlr_output_configuration_head_v1::Request::SetCustomMode {
+                width,
+                height,
+                refresh,
+            } => {
+                if config.mode.is_some() || config.custom_mode.is_some() {
+                    return already_set(resource, "mode");
+                }
+                if width <= 0 || height <= 0 || refresh < 0 {
+                    resource.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidCustomMode,
+                        format!("invalid custom mode {}x{}@{}", width, height, refresh),
+                    );
+                    return;
+                }
+                config.custom_mode = Some(((width, height).into(), refresh));
+            }
+            zwlr_output_configuration_head_v1::Request::SetPosition { x, y } => {
+                if config.position.is_some() {
+                    return already_set(resource, "position");
+                }
+                config.position = Some((x, y).into());
+            }
+            zwlr_output_configuration_head_v1::Request::SetTransform { transform } => {
+                if config.transform.is_some() {
+                    return already_set(resource, "transform");
+                }
+                match transform {
+                    WEnum::Value(transform) => {
+                        config.transform = Some(Transform::from(transform));
+                    }
+                    WEnum::Unknown(value) => resource.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidTransform,
+                        format!("invalid transform {}", value),
+                    ),
+                }
+            }
+            zwlr_output_configuration_head_v1::Request::SetScale { scale } => {
+                if config.scale.is_some() {
+                    return already_set(resource, "scale");
+                }
+                if !scale.is_finite() || scale <= 0.0 {
+                    resource.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidScale,
+                        format!("invalid scale {}", scale),
+                    );
+                    return;
+                }
+                config.scale = Some(scale);
+            }
+            request => {
+                warn!(?request, "Unsupported output configuration request");
+            }
+        }
+    }
+}
+
+/// Implement the output management protocol for the given state type
+#[macro_export]
+macro_rules! delegate_output_management {
+    ($ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: $crate::output_management::OutputManagerGlobalData
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: ()
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_head_v1::ZwlrOutputHeadV1: String
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_mode_v1::ZwlrOutputModeV1: $crate::output_management::ModeData
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_v1::ZwlrOutputConfigurationV1: $crate::output_management::ConfigurationData
+        ] => $crate::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1: std::sync::Mutex<$crate::output_management::HeadConfiguration>
+        ] => $crate::output_management::OutputManagementState);
+    };
+}
@@ -0,0 +1,233 @@
+//! Server side of the `wlr-output-power-management-unstable-v1` protocol
+//!
+//! Turning an output on or off is left to the backend through [`OutputPowerHandler`], which
+//! reports every change with [`OutputPowerManagerState::mode_changed`], so clients watching the
+//! output learn about changes made through other ways like the control socket.
+
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::output_power_management::v1::server::{
+            zwlr_output_power_manager_v1::{self, ZwlrOutputPowerManagerV1},
+            zwlr_output_power_v1::{self, Mode, ZwlrOutputPowerV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+        },
+    },
+};
+use tracing::trace;
+
+const VERSION: u32 = 1;
+
+pub trait OutputPowerHandler {
+    fn output_power_state(&mut self) -> &mut OutputPowerManagerState;
+
+    /// Whether `output` is powered on
+    fn output_powered(&mut self, output: &Output) -> bool;
+
+    /// Turn `output` on or off, returns `false` if its power can't be changed
+    fn set_output_powered(&mut self, output: &Output, on: bool) -> bool;
+}
+
+pub struct OutputPowerManagerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+#[derive(Debug)]
+pub struct OutputPowerManagerState {
+    global: GlobalId,
+    /// Controls of outputs that are still valid
+    powers: Vec<ZwlrOutputPowerV1>,
+}
+
+fn mode(on: bool) -> Mode {
+    if on {
+        Mode::On
+    } else {
+        Mode::Off
+    }
+}
+
+impl OutputPowerManagerState {
+    /// Create the `zwlr_output_power_manager_v1` global, only visible to clients passing `filter`
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrOutputPowerManagerV1, OutputPowerManagerGlobalData>
+            + Dispatch<ZwlrOutputPowerManagerV1, ()>
+            + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        let global = display.create_global::<D, ZwlrOutputPowerManagerV1, _>(
+            VERSION,
+            OutputPowerManagerGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+
+        OutputPowerManagerState {
+            global,
+            powers: Vec::new(),
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Tell the clients controlling `output` that it was turned on or off
+    pub fn mode_changed(&self, output: &Output, on: bool) {
+        for power in self.powers.iter().filter(|power| controls(power, output)) {
+            power.mode(mode(on));
+        }
+    }
+
+    /// Invalidate the controls of an output that disappeared
+    pub fn output_removed(&mut self, output: &Output) {
+        self.powers.retain(|power| {
+            let removed = controls(power, output);
+            if removed {
+                power.failed();
+            }
+            !removed
+        });
+    }
+}
+
+fn controls(power: &ZwlrOutputPowerV1, output: &Output) -> bool {
+    power.data::<Option<Output>>() == Some(&Some(output.clone()))
+}
+
+impl<D> GlobalDispatch<ZwlrOutputPowerManagerV1, OutputPowerManagerGlobalData, D>
+    for OutputPowerManagerState
+where
+    D: GlobalDispatch<ZwlrOutputPowerManagerV1, OutputPowerManagerGlobalData>
+        + Dispatch<ZwlrOutputPowerManagerV1, ()>
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrOutputPowerManagerV1>,
+        _global_data: &OutputPowerManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &OutputPowerManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputPowerManagerV1, (), D> for OutputPowerManagerState
+where
+    D: Dispatch<ZwlrOutputPowerManagerV1, ()>
+        + Dispatch<ZwlrOutputPowerV1, Option<Output>>
+        + OutputPowerHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrOutputPowerManagerV1,
+        request: zwlr_output_power_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_power_manager_v1::Request::GetOutputPower { id, output } => {
+                let output = Output::from_resource(&output);
+                trace!(output = ?output.as_ref().map(Output::name), "New output power control");
+                let power = data_init.init(id, output.clone());
+                match output {
+                    Some(output) => {
+                        power.mode(mode(state.output_powered(&output)));
+                        state.output_power_state().powers.push(power);
+                    }
+                    None => power.failed(),
+                }
+            }
+            zwlr_output_power_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputPowerV1, Option<Output>, D> for OutputPowerManagerState
+where
+    D: Dispatch<ZwlrOutputPowerV1, Option<Output>> + OutputPowerHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrOutputPowerV1,
+        request: zwlr_output_power_v1::Request,
+        data: &Option<Output>,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_power_v1::Request::SetMode { mode } => {
+                let on = match mode {
+                    WEnum::Value(Mode::On) => true,
+                    WEnum::Value(Mode::Off) => false,
+                    _ => {
+                        resource.post_error(
+                            zwlr_output_power_v1::Error::InvalidMode,
+                            "unknown power mode",
+                        );
+                        return;
+                    }
+                };
+                // failed was already sent for controls without an output
+                let Some(output) = data else {
+                    return;
+                };
+                if !state.output_power_state().powers.contains(resource) {
+                    return;
+                }
+                if !state.set_output_powered(output, on) {
+                    resource.failed();
+                    state
+                        .output_power_state()
+                        .powers
+                        .retain(|power| power != resource);
+                }
+            }
+            zwlr_output_power_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrOutputPowerV1,
+        _data: &Option<Output>,
+    ) {
+        state
+            .output_power_state()
+            .powers
+            .retain(|power| power != resource);
+    }
+}
+
+/// Implement the output power management protocol for the given state type
+#[macro_export]
+macro_rules! delegate_output_power {
+    ($ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1: $crate::output_power::OutputPowerManagerGlobalData
+        ] => $crate::output_power::OutputPowerManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1: ()
+        ] => $crate::output_power::OutputPowerManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_v1::ZwlrOutputPowerV1: Option<smithay::output::Output>
+        ] => $crate::output_power::OutputPowerManagerState);
+    };
+}
@@ -2,10 +2,11 @@ use smithay::{
     backend::renderer::{
         damage::{Error as OutputDamageTrackerError, OutputDamageTracker, RenderOutputResult},
         element::{
+            solid::SolidColorRenderElement,
             surface::WaylandSurfaceRenderElement,
             utils::{
-                ConstrainAlign, ConstrainScaleBehavior, CropRenderElement, RelocateRenderElement,
-                RescaleRenderElement,
+                ConstrainAlign, ConstrainScaleBehavior, CropRenderElement, Relocate,
+                RelocateRenderElement, RescaleRenderElement,
             },
             AsRenderElements, RenderElement, Wrap,
         },
@@ -19,13 +20,14 @@ use smithay::{
         },
     },
     output::Output,
-    utils::{Point, Rectangle, Size},
+    utils::{Physical, Point, Rectangle, Scale, Size},
 };
 
 #[cfg(feature = "debug")]
 use crate::drawing::FpsElement;
 use crate::{
-    drawing::{PointerRenderElement, CLEAR_COLOR, CLEAR_COLOR_FULLSCREEN},
+    config::{ColorConfig, MirrorScaling},
+    drawing::PointerRenderElement,
     shell::{FullscreenSurface, WindowElement, WindowRenderElement},
 };
 
@@ -34,6 +36,7 @@ smithay::backend::renderer::element::render_elements! {
         R: ImportAll + ImportMem;
     Pointer=PointerRenderElement<R>,
     Surface=WaylandSurfaceRenderElement<R>,
+    Dim=SolidColorRenderElement,
     #[cfg(feature = "debug")]
     // Note: We would like to borrow this element instead, but that would introduce
     // a feature-dependent lifetime, which introduces a lot more feature bounds
@@ -47,6 +50,7 @@ impl<R: Renderer> std::fmt::Debug for CustomRenderElements<R> {
         match self {
             Self::Pointer(arg0) => f.debug_tuple("Pointer").field(arg0).finish(),
             Self::Surface(arg0) => f.debug_tuple("Surface").field(arg0).finish(),
+            Self::Dim(arg0) => f.debug_tuple("Dim").field(arg0).finish(),
             #[cfg(feature = "debug")]
             Self::Fps(arg0) => f.debug_tuple("Fps").field(arg0).finish(),
             Self::_GenericCatcher(arg0) => f.debug_tuple("_GenericCatcher").field(arg0).finish(),
@@ -76,6 +80,90 @@ impl<R: Renderer + ImportAll + ImportMem, E: RenderElement<R> + std::fmt::Debug>
     }
 }
 
+/// The elements without the pointer, which is always rendered on top
+pub fn without_cursor<R, E>(
+    elements: &[OutputRenderElements<R, E>],
+) -> &[OutputRenderElements<R, E>]
+where
+    R: Renderer + ImportAll + ImportMem,
+    E: RenderElement<R>,
+{
+    let cursor = elements
+        .iter()
+        .take_while(|element| {
+            matches!(
+                element,
+                OutputRenderElements::Custom(CustomRenderElements::Pointer(_))
+            )
+        })
+        .count();
+    &elements[cursor..]
+}
+
+pub type MirrorRenderElement<R, E> =
+    RelocateRenderElement<RescaleRenderElement<OutputRenderElements<R, E>>>;
+
+/// Scale and offset the upright content of `source` is shown with on the mirror `output`
+///
+/// `None` if the source has no mode.
+pub fn mirror_placement(
+    source: &Output,
+    output: &Output,
+    scaling: MirrorScaling,
+) -> Option<(Scale<f64>, Point<f64, Physical>)> {
+    let transformed_size = |output: &Output| {
+        output
+            .current_mode()
+            .map(|mode| output.current_transform().transform_size(mode.size))
+            .unwrap_or_default()
+            .to_f64()
+    };
+    let source_size = transformed_size(source);
+    let output_size = transformed_size(output);
+    if source_size.w <= 0.0 || source_size.h <= 0.0 {
+        return None;
+    }
+
+    let scale = Scale::from((output_size.w / source_size.w, output_size.h / source_size.h));
+    let scale = match scaling {
+        MirrorScaling::Letterbox => Scale::from(f64::min(scale.x, scale.y)),
+        MirrorScaling::Stretch => scale,
+    };
+    let offset = Point::from((
+        (output_size.w - source_size.w * scale.x) / 2.0,
+        (output_size.h - source_size.h * scale.y) / 2.0,
+    ));
+    Some((scale, offset))
+}
+
+/// Fit the elements rendered for `source` onto `output`
+pub fn mirror_elements<R, E>(
+    elements: Vec<OutputRenderElements<R, E>>,
+    source: &Output,
+    output: &Output,
+    scaling: MirrorScaling,
+) -> Vec<MirrorRenderElement<R, E>>
+where
+    R: Renderer + ImportAll + ImportMem,
+    E: RenderElement<R>,
+{
+    let Some((scale, offset)) = mirror_placement(source, output, scaling) else {
+        return Vec::new();
+    };
+    let offset = offset.to_i32_round();
+
+    elements
+        .into_iter()
+        .map(|element| {
+            RelocateRenderElement::from_element(
+                RescaleRenderElement::from_element(element, (0, 0).into(), scale),
+                offset,
+                Relocate::Relative,
+            )
+        })
+        .collect()
+}
+
 pub fn space_preview_elements<'a, R, C>(
     renderer: &'a mut R,
     space: &'a Space<WindowElement>,
@@ -147,6 +235,7 @@ pub fn output_elements<R>(
     custom_elements: impl IntoIterator<Item = CustomRenderElements<R>>,
     renderer: &mut R,
     show_window_preview: bool,
+    colors: &ColorConfig,
 ) -> (
     Vec<OutputRenderElements<R, WindowRenderElement<R>>>,
     [f32; 4],
@@ -200,7 +289,7 @@ where
                     .map(|e| OutputRenderElements::Window(Wrap::from(e))),
             )
             .collect::<Vec<_>>();
-        (elements, CLEAR_COLOR_FULLSCREEN)
+        (elements, colors.fullscreen.0)
     } else {
         let mut output_render_elements = custom_elements
             .into_iter()
@@ -220,7 +309,7 @@ where
         .expect("output without mode?");
         output_render_elements.extend(space_elements.into_iter().map(OutputRenderElements::Space));
 
-        (output_render_elements, CLEAR_COLOR)
+        (output_render_elements, colors.background.0)
     }
 }
 
@@ -233,6 +322,7 @@ pub fn render_output<R>(
     damage_tracker: &mut OutputDamageTracker,
     age: usize,
     show_window_preview: bool,
+    colors: &ColorConfig,
 ) -> Result<RenderOutputResult, OutputDamageTrackerError<R>>
 where
     R: Renderer + ImportAll + ImportMem,
@@ -244,6 +334,7 @@ where
         custom_elements,
         renderer,
         show_window_preview,
+        colors,
     );
     damage_tracker.render_output(renderer, age, &elements, clear_color)
 }
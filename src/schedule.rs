@@ -0,0 +1,189 @@
+//! Turning the outputs on and off at fixed local times
+//!
+//! The schedule is evaluated again at every scheduled change, but at least once a minute, as the
+//! wall clock might be adjusted at any time, e.g. by NTP shortly after boot. An override keeps
+//! the outputs on or off for a while regardless of the schedule.
+
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Days, Local, NaiveDateTime};
+use smithay::reexports::calloop::{
+    timer::{TimeoutAction, Timer},
+    RegistrationToken,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config::ScheduleConfig,
+    state::{Backend, BuedchenState},
+};
+
+/// Longest time between two evaluations of the schedule
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+struct ScheduleOverride {
+    on: bool,
+    until: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct ScheduleState {
+    /// Outputs are currently turned off by the schedule or an override
+    off: bool,
+    /// Clients are currently stopped by the schedule
+    clients_stopped: bool,
+    override_: Option<ScheduleOverride>,
+    timer: Option<RegistrationToken>,
+}
+
+impl ScheduleState {
+    pub fn is_off(&self) -> bool {
+        self.off
+    }
+}
+
+/// Periods the outputs are on that overlap the week around `now`
+fn on_periods(
+    config: &ScheduleConfig,
+    now: NaiveDateTime,
+) -> impl Iterator<Item = (NaiveDateTime, NaiveDateTime)> + '_ {
+    // a period started yesterday might last past midnight
+    let first_day = now.date() - Days::new(1);
+    (0..9)
+        .filter_map(move |offset| first_day.checked_add_days(Days::new(offset)))
+        .flat_map(move |date| {
+            config
+                .periods
+                .iter()
+                .filter(move |period| {
+                    period
+                        .days
+                        .iter()
+                        .any(|day| day.weekday() == date.weekday())
+                })
+                .map(move |period| {
+                    let end_date = if period.off.0 <= period.on.0 {
+                        date + Days::new(1)
+                    } else {
+                        date
+                    };
+                    (date.and_time(period.on.0), end_date.and_time(period.off.0))
+                })
+        })
+}
+
+/// Whether the schedule has the outputs on at `now`, always if there are no periods
+pub fn scheduled_on(config: &ScheduleConfig, now: NaiveDateTime) -> bool {
+    config.periods.is_empty()
+        || on_periods(config, now).any(|(start, end)| start <= now && now < end)
+}
+
+/// Next time after `now` at which the schedule turns the outputs on or off
+pub fn next_change(config: &ScheduleConfig, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let current = scheduled_on(config, now);
+    let mut boundaries = on_periods(config, now)
+        .flat_map(|(start, end)| [start, end])
+        .filter(|time| *time > now)
+        .collect::<Vec<_>>();
+    boundaries.sort();
+    boundaries
+        .into_iter()
+        .find(|time| scheduled_on(config, *time) != current)
+}
+
+impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
+    /// Turn the outputs on or off as the schedule and override want and wait for the next change
+    pub fn apply_schedule(&mut self) {
+        if let Some(token) = self.schedule.timer.take() {
+            self.handle.remove(token);
+        }
+
+        let now = Local::now().naive_local();
+        let config = &self.config.schedule;
+        let mut timeout = next_change(config, now)
+            .and_then(|next| (next - now).to_std().ok())
+            .map_or(MAX_CHECK_INTERVAL, |timeout| {
+                timeout.min(MAX_CHECK_INTERVAL)
+            });
+
+        let mut on = scheduled_on(config, now);
+        if let Some(override_) = self.schedule.override_ {
+            match override_.until.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => {
+                    on = override_.on;
+                    timeout = timeout.min(remaining);
+                }
+                _ => {
+                    info!("Schedule override expired");
+                    self.schedule.override_ = None;
+                }
+            }
+        }
+        self.set_scheduled_off(!on);
+
+        // without periods there is nothing to wait for, besides an override running out
+        if self.config.schedule.periods.is_empty() && self.schedule.override_.is_none() {
+            return;
+        }
+        let res = self
+            .handle
+            .insert_source(Timer::from_duration(timeout), |_, _, data| {
+                // the timer is dropped here, don't remove it while it's running
+                data.state.schedule.timer = None;
+                data.state.apply_schedule();
+                TimeoutAction::Drop
+            });
+        match res {
+            Ok(token) => self.schedule.timer = Some(token),
+            Err(err) => warn!("Failed to start the schedule timer: {}", err),
+        }
+    }
+
+    /// Keep the outputs on or off for `duration` regardless of the schedule
+    pub fn override_schedule(&mut self, on: bool, duration: Duration) -> Result<(), String> {
+        let until = Instant::now()
+            .checked_add(duration)
+            .ok_or("Override duration is too long")?;
+        info!(on, ?duration, "Overriding the schedule");
+        self.schedule.override_ = Some(ScheduleOverride { on, until });
+        self.apply_schedule();
+        Ok(())
+    }
+
+    /// Follow the schedule again
+    pub fn clear_schedule_override(&mut self) {
+        if self.schedule.override_.take().is_some() {
+            info!("Schedule override cleared");
+        }
+        self.apply_schedule();
+    }
+
+    fn set_scheduled_off(&mut self, off: bool) {
+        if self.schedule.off != off {
+            info!(off, "Scheduled output power change");
+            self.schedule.off = off;
+            if !off {
+                // start the idle timeouts over, so the outputs don't blank right away
+                self.notify_activity();
+            }
+            self.update_blanking();
+        }
+        self.update_stopped_clients();
+    }
+
+    /// Stop or resume the clients as the schedule wants, `stop_client` might change on reload
+    fn update_stopped_clients(&mut self) {
+        let stop = self.schedule.off && self.config.schedule.stop_client;
+        if self.schedule.clients_stopped == stop {
+            return;
+        }
+
+        self.schedule.clients_stopped = stop;
+        if stop {
+            self.stop_clients();
+        } else {
+            self.resume_clients();
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! Server side of the `wlr-screencopy-unstable-v1` protocol
+//!
+//! Frames are validated here and handed to the backend through [`ScreencopyHandler`],
+//! which renders the output into the client buffer and completes the [`Screencopy`].
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use smithay::{
+    backend::allocator::Fourcc,
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            backend::GlobalId,
+            protocol::{wl_buffer::WlBuffer, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Buffer, Logical, Physical, Rectangle, Size},
+    wayland::{dmabuf::get_dmabuf, shm::with_buffer_contents},
+};
+use tracing::trace;
+
+const VERSION: u32 = 3;
+
+pub trait ScreencopyHandler {
+    /// A client wants the content of [`Screencopy::output`] copied into its buffer
+    ///
+    /// Dropping the screencopy without submitting it reports a failed copy.
+    fn frame(&mut self, screencopy: Screencopy);
+}
+
+pub struct ScreencopyManagerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+/// What a frame is going to capture, `None` if the capture failed right away
+pub struct FrameData {
+    capture: Option<Capture>,
+    used: AtomicBool,
+}
+
+#[derive(Debug, Clone)]
+struct Capture {
+    output: Output,
+    /// Captured part of the output in buffer coordinates of the output
+    region: Rectangle<i32, Buffer>,
+    overlay_cursor: bool,
+}
+
+impl Capture {
+    fn is_full_output(&self) -> bool {
+        Some(self.region) == capture_region(&self.output, None)
+    }
+}
+
+#[derive(Debug)]
+pub struct ScreencopyManagerState {
+    global: GlobalId,
+}
+
+impl ScreencopyManagerState {
+    /// Create the `zwlr_screencopy_manager_v1` global, only visible to clients passing `filter`
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyManagerGlobalData>
+            + Dispatch<ZwlrScreencopyManagerV1, ()>
+            + 'static,
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+    {
+        let global = display.create_global::<D, ZwlrScreencopyManagerV1, _>(
+            VERSION,
+            ScreencopyManagerGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+
+        ScreencopyManagerState { global }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+/// A frame with a buffer attached, waiting to be rendered into
+#[derive(Debug)]
+pub struct Screencopy {
+    frame: ZwlrScreencopyFrameV1,
+    buffer: WlBuffer,
+    capture: Capture,
+    with_damage: bool,
+    submitted: bool,
+}
+
+impl Screencopy {
+    pub fn output(&self) -> &Output {
+        &self.capture.output
+    }
+
+    pub fn buffer(&self) -> &WlBuffer {
+        &self.buffer
+    }
+
+    /// Part of the output to copy, in buffer coordinates of the output
+    pub fn region(&self) -> Rectangle<i32, Buffer> {
+        self.capture.region
+    }
+
+    /// Whether the cursor should be part of the copy
+    pub fn overlay_cursor(&self) -> bool {
+        self.capture.overlay_cursor
+    }
+
+    /// Whether the whole output is copied, only then dmabufs are offered as target
+    pub fn is_full_output(&self) -> bool {
+        self.capture.is_full_output()
+    }
+
+    /// Whether the copy should wait until the output content changed
+    pub fn with_damage(&self) -> bool {
+        self.with_damage
+    }
+
+    /// Report a successful copy
+    ///
+    /// `damage` is the damage of the output since the last damage-tracked copy.
+    pub fn submit(mut self, y_invert: bool, damage: &[Rectangle<i32, Physical>], time: Duration) {
+        self.frame.flags(if y_invert {
+            zwlr_screencopy_frame_v1::Flags::YInvert
+        } else {
+            zwlr_screencopy_frame_v1::Flags::empty()
+        });
+
+        if self.with_damage {
+            let transform = self.capture.output.current_transform();
+            let area = output_size(&self.capture.output)
+                .map(|size| transform.transform_size(size).to_logical(1))
+                .unwrap_or_default();
+            for rect in damage {
+                let rect = rect.to_logical(1).to_buffer(1, transform, &area);
+                if let Some(rect) = rect.intersection(self.capture.region) {
+                    let loc = rect.loc - self.capture.region.loc;
+                    self.frame.damage(
+                        loc.x as u32,
+                        loc.y as u32,
+                        rect.size.w as u32,
+                        rect.size.h as u32,
+                    );
+                }
+            }
+        }
+
+        let secs = time.as_secs();
+        self.frame
+            .ready((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+        self.submitted = true;
+    }
+}
+
+impl Drop for Screencopy {
+    fn drop(&mut self) {
+        if !self.submitted {
+            self.frame.failed();
+        }
+    }
+}
+
+fn output_size(output: &Output) -> Option<Size<i32, Physical>> {
+    output.current_mode().map(|mode| mode.size)
+}
+
+/// Captured region for a region of the output in its logical coordinates, or the whole output
+fn capture_region(
+    output: &Output,
+    region: Option<Rectangle<i32, Logical>>,
+) -> Option<Rectangle<i32, Buffer>> {
+    let mode_size = output_size(output)?;
+    let full = Rectangle::from_loc_and_size((0, 0), (mode_size.w, mode_size.h));
+    let Some(region) = region else {
+        return Some(full);
+    };
+
+    let scale = output.current_scale().fractional_scale();
+    let transform = output.current_transform();
+    let area = transform
+        .transform_size(mode_size)
+        .to_f64()
+        .to_logical(scale);
+    region
+        .to_f64()
+        .to_buffer(scale, transform, &area)
+        .to_i32_round()
+        .intersection(full)
+        .filter(|region| region.size.w > 0 && region.size.h > 0)
+}
+
+fn init_frame<D>(
+    data_init: &mut DataInit<'_, D>,
+    frame: New<ZwlrScreencopyFrameV1>,
+    capture: Option<Capture>,
+) where
+    D: Dispatch<ZwlrScreencopyFrameV1, FrameData> + 'static,
+{
+    let frame = data_init.init(
+        frame,
+        FrameData {
+            capture: capture.clone(),
+            used: AtomicBool::new(false),
+        },
+    );
+    let Some(capture) = capture else {
+        frame.failed();
+        return;
+    };
+
+    let size = capture.region.size;
+    frame.buffer(
+        wl_shm::Format::Xrgb8888,
+        size.w as u32,
+        size.h as u32,
+        size.w as u32 * 4,
+    );
+    if frame.version() >= 3 {
+        // rendering into a dmabuf always covers the whole output
+        if capture.is_full_output() {
+            frame.linux_dmabuf(Fourcc::Xrgb8888 as u32, size.w as u32, size.h as u32);
+        }
+        frame.buffer_done();
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyManagerGlobalData, D>
+    for ScreencopyManagerState
+where
+    D: GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyManagerGlobalData>
+        + Dispatch<ZwlrScreencopyManagerV1, ()>
+        + 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _global_data: &ScreencopyManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &ScreencopyManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrScreencopyManagerV1, (), D> for ScreencopyManagerState
+where
+    D: Dispatch<ZwlrScreencopyManagerV1, ()> + Dispatch<ZwlrScreencopyFrameV1, FrameData> + 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _data: &(),
+        _dhandle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let (frame, overlay_cursor, output, region) = match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput {
+                frame,
+                overlay_cursor,
+                output,
+            } => (frame, overlay_cursor, output, None),
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                frame,
+                overlay_cursor,
+                output,
+                x,
+                y,
+                width,
+                height,
+            } => (
+                frame,
+                overlay_cursor,
+                output,
+                Some(Rectangle::from_loc_and_size((x, y), (width, height))),
+            ),
+            zwlr_screencopy_manager_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        let capture = Output::from_resource(&output).and_then(|output| {
+            let region = capture_region(&output, region)?;
+            Some(Capture {
+                output,
+                region,
+                overlay_cursor: overlay_cursor != 0,
+            })
+        });
+        trace!(?capture, "New screencopy frame");
+        init_frame(data_init, frame, capture);
+    }
+}
+
+impl<D> Dispatch<ZwlrScreencopyFrameV1, FrameData, D> for ScreencopyManagerState
+where
+    D: Dispatch<ZwlrScreencopyFrameV1, FrameData> + ScreencopyHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        data: &FrameData,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let (buffer, with_damage) = match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => (buffer, false),
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => (buffer, true),
+            zwlr_screencopy_frame_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        if data.used.swap(true, Ordering::SeqCst) {
+            resource.post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed,
+                "frame was already copied",
+            );
+            return;
+        }
+        let Some(capture) = data.capture.clone() else {
+            // failed was already sent
+            return;
+        };
+
+        let size = capture.region.size;
+        let valid = if let Ok(dmabuf) = get_dmabuf(&buffer) {
+            dmabuf.size() == size && capture.is_full_output()
+        } else {
+            with_buffer_contents(&buffer, |_, len, buffer_data| {
+                matches!(
+                    buffer_data.format,
+                    wl_shm::Format::Xrgb8888 | wl_shm::Format::Argb8888
+                ) && buffer_data.width == size.w
+                    && buffer_data.height == size.h
+                    && buffer_data.stride >= size.w * 4
+                    && buffer_data.offset as usize + buffer_data.stride as usize * size.h as usize
+                        <= len
+            })
+            .unwrap_or(false)
+        };
+        if !valid {
+            resource.post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer,
+                "buffer does not match the advertised parameters",
+            );
+            return;
+        }
+
+        state.frame(Screencopy {
+            frame: resource.clone(),
+            buffer,
+            capture,
+            with_damage,
+            submitted: false,
+        });
+    }
+}
+
+/// Implement the screencopy protocol for the given state type
+#[macro_export]
+macro_rules! delegate_screencopy {
+    ($ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1: $crate::screencopy::ScreencopyManagerGlobalData
+        ] => $crate::screencopy::ScreencopyManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1: ()
+        ] => $crate::screencopy::ScreencopyManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1: $crate::screencopy::FrameData
+        ] => $crate::screencopy::ScreencopyManagerState);
+    };
+}
@@ -0,0 +1,209 @@
+//! Screenshots of whole outputs written as PNG files
+//!
+//! Outputs are rendered offscreen from the same elements as on screen, only without the
+//! cursor, and turned upright according to the output transform. Only the readback happens on
+//! the event loop, encoding and writing the PNG is left to a separate thread.
+
+use std::{
+    error::Error,
+    path::PathBuf,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::{Error as OutputDamageTrackerError, OutputDamageTracker},
+            Bind, ExportMem, ImportAll, ImportMem, Offscreen, Renderer, TextureMapping,
+        },
+    },
+    desktop::Space,
+    output::Output,
+    reexports::calloop::{
+        signals::{Signal, Signals},
+        LoopHandle, RegistrationToken,
+    },
+    utils::{Buffer, Rectangle, Size, Transform},
+};
+use tracing::{error, info};
+
+use crate::{
+    config::ColorConfig,
+    render::{output_elements, CustomRenderElements},
+    shell::WindowElement,
+    state::{Backend, BuedchenState, CalloopData},
+};
+
+/// Tightly packed RGBA pixels of an output
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Screenshot {
+    /// Encode and write the screenshot of `output` to `path` on a separate thread
+    ///
+    /// `done` is called on that thread with the result once the file is written.
+    pub fn save(
+        self,
+        output: String,
+        path: PathBuf,
+        done: impl FnOnce(Result<(), String>) + Send + 'static,
+    ) -> Result<(), String> {
+        thread::Builder::new()
+            .name(String::from("screenshot"))
+            .spawn(move || {
+                let res = image::save_buffer_with_format(
+                    &path,
+                    &self.pixels,
+                    self.width,
+                    self.height,
+                    image::ColorType::Rgba8,
+                    image::ImageFormat::Png,
+                )
+                .map_err(|err| format!("Failed to write {}: {}", path.display(), err));
+                match &res {
+                    Ok(()) => info!(output = %output, path = %path.display(), "Saved screenshot"),
+                    Err(err) => error!("{}", err),
+                }
+                done(res);
+            })
+            .map_err(|err| format!("Failed to start writing {}: {}", path.display(), err))?;
+        Ok(())
+    }
+}
+
+/// Render `output` into an offscreen buffer of type `T` and read it back
+pub fn render_screenshot<R, T>(
+    renderer: &mut R,
+    output: &Output,
+    space: &Space<WindowElement>,
+    show_window_preview: bool,
+    colors: &ColorConfig,
+) -> Result<Screenshot, Box<dyn Error>>
+where
+    R: Renderer + ImportAll + ImportMem + Offscreen<T> + Bind<T> + ExportMem,
+    R::TextureId: Clone + 'static,
+    R::Error: 'static,
+{
+    let mode = output.current_mode().ok_or("output has no mode")?;
+    let size = output.current_transform().transform_size(mode.size);
+    let buffer_size = Size::<i32, Buffer>::from((size.w, size.h));
+
+    let (elements, clear_color) = output_elements(
+        output,
+        space,
+        std::iter::empty::<CustomRenderElements<R>>(),
+        renderer,
+        show_window_preview,
+        colors,
+    );
+
+    let target = renderer.create_buffer(Fourcc::Abgr8888, buffer_size)?;
+    renderer.bind(target)?;
+    // the elements are already laid out for the transformed output, so render them upright
+    OutputDamageTracker::new(
+        size,
+        output.current_scale().fractional_scale(),
+        Transform::Normal,
+    )
+    .render_output(renderer, 0, &elements, clear_color)
+    .map_err(|err| -> Box<dyn Error> {
+        match err {
+            OutputDamageTrackerError::Rendering(err) => Box::new(err),
+            OutputDamageTrackerError::OutputNoMode(err) => Box::new(err),
+        }
+    })?;
+
+    let mapping = renderer.copy_framebuffer(
+        Rectangle::from_loc_and_size((0, 0), buffer_size),
+        Fourcc::Abgr8888,
+    )?;
+    let data = renderer.map_texture(&mapping)?;
+    let row_length = size.w as usize * 4;
+    let pixels = if mapping.flipped() {
+        data.chunks_exact(row_length)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    } else {
+        data.to_vec()
+    };
+
+    Ok(Screenshot {
+        width: size.w as u32,
+        height: size.h as u32,
+        pixels,
+    })
+}
+
+/// Take screenshots of all outputs on `SIGUSR1`
+pub fn screenshot_on_sigusr1<BackendData: Backend + 'static>(
+    handle: &LoopHandle<'static, CalloopData<BackendData>>,
+) -> Result<RegistrationToken, Box<dyn Error>> {
+    let signals = Signals::new(&[Signal::SIGUSR1])?;
+    let token = handle
+        .insert_source(signals, move |_, _, data| {
+            info!("Received SIGUSR1, taking screenshots");
+            data.state.screenshot_all();
+        })
+        .map_err(|err| err.error)?;
+    Ok(token)
+}
+
+impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
+    /// Write a screenshot of the named output, or the first one, to `path`
+    ///
+    /// Returns once the output is rendered, `done` gets the result of writing the file.
+    pub fn screenshot(
+        &mut self,
+        output: Option<&str>,
+        path: PathBuf,
+        done: impl FnOnce(Result<(), String>) + Send + 'static,
+    ) -> Result<(), String> {
+        let output = match output {
+            Some(name) => self.space.outputs().find(|o| o.name() == name),
+            None => self.space.outputs().next(),
+        }
+        .cloned()
+        .ok_or_else(|| match output {
+            Some(name) => format!("No output named {}", name),
+            None => String::from("No outputs"),
+        })?;
+
+        let screenshot = self
+            .backend_data
+            .render_screenshot(
+                &output,
+                &self.space,
+                self.show_window_preview,
+                &self.config.colors,
+            )
+            .map_err(|err| format!("Failed to render {}: {}", output.name(), err))?;
+        screenshot.save(output.name(), path, done)
+    }
+
+    /// Write screenshots of all outputs into the configured directory
+    pub fn screenshot_all(&mut self) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let outputs = self.space.outputs().map(Output::name).collect::<Vec<_>>();
+        for output in outputs {
+            let path: PathBuf = self
+                .config
+                .screenshot
+                .directory
+                .join(format!("buedchen-{}-{}.png", output, timestamp));
+            // errors writing the files are logged by the writing thread
+            if let Err(err) = self.screenshot(Some(&output), path, |_| {}) {
+                error!("{}", err);
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use smithay::{
     backend::renderer::utils::on_commit_buffer_handler,
@@ -89,6 +89,34 @@ impl FullscreenSurface {
     }
 }
 
+/// Configured position of an output, outputs without one are placed by [`fixup_positions`]
+#[derive(Default)]
+pub struct OutputPosition(Cell<Option<Point<i32, Logical>>>);
+
+impl OutputPosition {
+    pub fn set(&self, position: Option<Point<i32, Logical>>) {
+        self.0.set(position);
+    }
+
+    pub fn get(&self) -> Option<Point<i32, Logical>> {
+        self.0.get()
+    }
+}
+
+/// Name of the output a window is kept on, stored in the user data of every window
+#[derive(Default)]
+pub struct OutputPin(RefCell<Option<String>>);
+
+impl OutputPin {
+    pub fn set(&self, output: Option<String>) {
+        *self.0.borrow_mut() = output;
+    }
+
+    pub fn get(&self) -> Option<String> {
+        self.0.borrow().clone()
+    }
+}
+
 impl<BackendData: Backend> BufferHandler for BuedchenState<BackendData> {
     fn buffer_destroyed(&mut self, _buffer: &WlBuffer) {}
 }
@@ -145,6 +173,7 @@ impl<BackendData: Backend> CompositorHandler for BuedchenState<BackendData> {
             }
             if let Some(window) = self.window_for_surface(&root) {
                 window.0.on_commit();
+                self.pin_by_app_id(&window);
             }
         }
         self.popups.commit(surface);
@@ -202,6 +231,41 @@ impl<BackendData: Backend> BuedchenState<BackendData> {
             .find(|window| window.wl_surface().map(|s| s == *surface).unwrap_or(false))
             .cloned()
     }
+
+    /// Pin a window to the output of its application once its app id is known
+    fn pin_by_app_id(&mut self, window: &WindowElement) {
+        if self.apps.is_empty() {
+            return;
+        }
+        let Some(pin) = window.0.user_data().get::<OutputPin>() else {
+            return;
+        };
+        if pin.get().is_some() {
+            return;
+        }
+
+        let app_id = with_states(window.0.toplevel().wl_surface(), |states| {
+            states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .app_id
+                .clone()
+        });
+        let Some(output) = self.pinned_output(None, app_id.as_deref()) else {
+            return;
+        };
+        info!(app_id, output, "Pinning window to output");
+        pin.set(Some(output));
+        place_new_window(
+            &mut self.space,
+            self.pointer.current_location(),
+            window,
+            false,
+        );
+    }
 }
 
 #[derive(Default)]
@@ -323,7 +387,7 @@ fn ensure_initial_configure(
     };
 }
 
-fn resize_toplevel_windows(space: &Space<WindowElement>, map: &LayerMap, output: &Output) {
+pub fn resize_toplevel_windows(space: &Space<WindowElement>, map: &LayerMap, output: &Output) {
     let geo = space.output_geometry(&output).unwrap();
     let zone = map.non_exclusive_zone();
     let rect = Rectangle::from_loc_and_size(geo.loc + zone.loc, zone.size);
@@ -344,9 +408,14 @@ fn place_new_window(
     activate: bool,
 ) {
     info!("new window");
-    let output = space
-        .output_under(pointer_location)
-        .next()
+    let pinned = window
+        .0
+        .user_data()
+        .get::<OutputPin>()
+        .and_then(OutputPin::get);
+    let output = pinned
+        .and_then(|name| space.outputs().find(|o| o.name() == name))
+        .or_else(|| space.output_under(pointer_location).next())
         .or_else(|| space.outputs().next())
         .cloned();
     let output_geometry = output
@@ -371,9 +440,31 @@ fn place_new_window(
 }
 
 pub fn fixup_positions(space: &mut Space<WindowElement>, pointer_location: Point<f64, Logical>) {
-    // fixup outputs
+    // fixup outputs, the ones with a configured position are placed first
+    let outputs = space.outputs().cloned().collect::<Vec<_>>();
+    let (positioned, unpositioned): (Vec<_>, Vec<_>) = outputs.into_iter().partition(|output| {
+        output
+            .user_data()
+            .get::<OutputPosition>()
+            .and_then(OutputPosition::get)
+            .is_some()
+    });
     let mut offset = Point::<i32, Logical>::from((0, 0));
-    for output in space.outputs().cloned().collect::<Vec<_>>().into_iter() {
+    for output in positioned {
+        let size = space
+            .output_geometry(&output)
+            .map(|geo| geo.size)
+            .unwrap_or_else(|| Size::from((0, 0)));
+        let position = output
+            .user_data()
+            .get::<OutputPosition>()
+            .and_then(OutputPosition::get)
+            .unwrap();
+        space.map_output(&output, position);
+        layer_map_for_output(&output).arrange();
+        offset.x = offset.x.max(position.x + size.w);
+    }
+    for output in unpositioned {
         let size = space
             .output_geometry(&output)
             .map(|geo| geo.size)
@@ -383,7 +474,7 @@ pub fn fixup_positions(space: &mut Space<WindowElement>, pointer_location: Point
         offset.x += size.w;
     }
 
-    // fixup windows
+    // fixup windows, pinned ones are moved back to their output once it is available
     let mut orphaned_windows = Vec::new();
     let outputs = space
         .outputs()
@@ -391,7 +482,10 @@ pub fn fixup_positions(space: &mut Space<WindowElement>, pointer_location: Point
             let geo = space.output_geometry(o)?;
             let map = layer_map_for_output(o);
             let zone = map.non_exclusive_zone();
-            Some(Rectangle::from_loc_and_size(geo.loc + zone.loc, zone.size))
+            Some((
+                o.name(),
+                Rectangle::from_loc_and_size(geo.loc + zone.loc, zone.size),
+            ))
         })
         .collect::<Vec<_>>();
     for window in space.elements() {
@@ -401,7 +495,17 @@ pub fn fixup_positions(space: &mut Space<WindowElement>, pointer_location: Point
         };
         let geo_loc = window.bbox().loc + window_location;
 
-        if !outputs.iter().any(|o_geo| o_geo.contains(geo_loc)) {
+        let pinned = window
+            .0
+            .user_data()
+            .get::<OutputPin>()
+            .and_then(OutputPin::get)
+            .and_then(|pinned| outputs.iter().find(|(name, _)| *name == pinned));
+        let misplaced = match pinned {
+            Some((_, o_geo)) => !o_geo.contains(geo_loc),
+            None => !outputs.iter().any(|(_, o_geo)| o_geo.contains(geo_loc)),
+        };
+        if misplaced {
             orphaned_windows.push(window.clone());
         }
     }
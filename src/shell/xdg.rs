@@ -32,8 +32,8 @@ use crate::{
 };
 
 use super::{
-    fullscreen_output_geometry, place_new_window, FullscreenSurface, MoveSurfaceGrab, ResizeData,
-    ResizeState, ResizeSurfaceGrab, SurfaceData, WindowElement,
+    fullscreen_output_geometry, place_new_window, FullscreenSurface, MoveSurfaceGrab, OutputPin,
+    ResizeData, ResizeState, ResizeSurfaceGrab, SurfaceData, WindowElement,
 };
 
 impl<BackendData: Backend> XdgShellHandler for BuedchenState<BackendData> {
@@ -47,6 +47,14 @@ impl<BackendData: Backend> XdgShellHandler for BuedchenState<BackendData> {
         // of a xdg_surface has to be sent during the commit if
         // the surface is not already configured
         let window = WindowElement(Window::new(surface));
+        let pid = self.client_pid(window.0.toplevel().wl_surface());
+        window.0.user_data().insert_if_missing(OutputPin::default);
+        window
+            .0
+            .user_data()
+            .get::<OutputPin>()
+            .unwrap()
+            .set(self.pinned_output(pid, None));
         place_new_window(
             &mut self.space,
             self.pointer.current_location(),
@@ -223,18 +231,20 @@ impl<BackendData: Backend> XdgShellHandler for BuedchenState<BackendData> {
             // independently from its buffer size
             let wl_surface = surface.wl_surface();
 
+            // pinned windows stay on the output of their application, whatever the client asks for
+            let pinned = self
+                .window_for_surface(wl_surface)
+                .and_then(|window| window.0.user_data().get::<OutputPin>()?.get())
+                .and_then(|name| self.space.outputs().find(|o| o.name() == name).cloned());
+            if let Some(pinned) = pinned {
+                let client = self.display_handle.get_client(wl_surface.id()).unwrap();
+                wl_output = pinned.client_outputs(&client).into_iter().last();
+            }
+
             let output_geometry =
                 fullscreen_output_geometry(wl_surface, wl_output.as_ref(), &mut self.space);
 
             if let Some(geometry) = output_geometry {
-                let output = wl_output
-                    .as_ref()
-                    .and_then(Output::from_resource)
-                    .unwrap_or_else(|| self.space.outputs().next().unwrap().clone());
-                let client = self.display_handle.get_client(wl_surface.id()).unwrap();
-                for output in output.client_outputs(&client) {
-                    wl_output = Some(output);
-                }
                 let window = self
                     .space
                     .elements()
@@ -244,7 +254,18 @@ impl<BackendData: Backend> XdgShellHandler for BuedchenState<BackendData> {
                             .map(|s| s == *wl_surface)
                             .unwrap_or(false)
                     })
-                    .unwrap();
+                    .unwrap()
+                    .clone();
+                // without a requested output stay on the current one, pinned windows rely on that
+                let output = wl_output
+                    .as_ref()
+                    .and_then(Output::from_resource)
+                    .or_else(|| self.space.outputs_for_element(&window).first().cloned())
+                    .unwrap_or_else(|| self.space.outputs().next().unwrap().clone());
+                let client = self.display_handle.get_client(wl_surface.id()).unwrap();
+                for output in output.client_outputs(&client) {
+                    wl_output = Some(output);
+                }
 
                 surface.with_pending_state(|state| {
                     state.states.set(xdg_toplevel::State::Fullscreen);
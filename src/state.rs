@@ -26,7 +26,6 @@ use smithay::{
         PopupKind, PopupManager, Space,
     },
     input::{
-        keyboard::{Keysym, XkbConfig},
         pointer::{CursorImageStatus, PointerHandle},
         Seat, SeatHandler, SeatState,
     },
@@ -40,7 +39,7 @@ use smithay::{
         wayland_server::{
             backend::{ClientData, ClientId, DisconnectReason},
             protocol::{wl_data_source::WlDataSource, wl_surface::WlSurface},
-            Display, DisplayHandle, Resource,
+            Client, Display, DisplayHandle, Resource,
         },
     },
     utils::{Clock, Monotonic, Rectangle},
@@ -97,7 +96,20 @@ use smithay::{
     },
 };
 
-use crate::{focus::FocusTarget, shell::WindowElement};
+use crate::{
+    client::{ClientSupervisor, KioskApp},
+    config::{ColorAdjustment, ColorConfig, Config, ReloadFn},
+    delegate_idle_inhibit, delegate_idle_notify,
+    focus::FocusTarget,
+    idle::IdleState,
+    idle_inhibit::{IdleInhibitHandler, IdleInhibitManagerState},
+    idle_notify::{IdleNotifierState, IdleNotifyHandler},
+    ipc::{self, IpcServer},
+    lockdown::LockdownState,
+    schedule::ScheduleState,
+    screenshot::Screenshot,
+    shell::WindowElement,
+};
 
 pub struct CalloopData<BackendData: Backend + 'static> {
     pub state: BuedchenState<BackendData>,
@@ -116,6 +128,13 @@ impl ClientData for ClientState {
     fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
 }
 
+/// Whether a client may use privileged protocols, i.e. it is not running inside a security context
+pub fn is_privileged(client: &Client) -> bool {
+    client
+        .get_data::<ClientState>()
+        .map_or(true, |client_state| client_state.security_context.is_none())
+}
+
 #[derive(Debug)]
 pub struct BuedchenState<BackendData: Backend + 'static> {
     pub backend_data: BackendData,
@@ -144,11 +163,16 @@ pub struct BuedchenState<BackendData: Backend + 'static> {
     pub xdg_shell_state: XdgShellState,
     pub presentation_state: PresentationState,
     pub fractional_scale_manager_state: FractionalScaleManagerState,
+    pub idle_notifier_state: IdleNotifierState,
+    pub idle_inhibit_state: IdleInhibitManagerState,
 
     pub dnd_icon: Option<WlSurface>,
 
     // input-related fields
-    pub suppressed_keys: Vec<Keysym>,
+    /// Keycodes of pressed keys that triggered a binding, their release isn't forwarded either
+    pub suppressed_keys: Vec<u32>,
+    /// Index of the configured keyboard layout in use, 0 is the main one
+    pub keyboard_layout: usize,
     pub cursor_status: Arc<Mutex<CursorImageStatus>>,
     pub seat_name: String,
     pub seat: Seat<BuedchenState<BackendData>>,
@@ -160,6 +184,17 @@ pub struct BuedchenState<BackendData: Backend + 'static> {
     pub renderdoc: Option<renderdoc::RenderDoc<renderdoc::V141>>,
 
     pub show_window_preview: bool,
+
+    pub client: ClientSupervisor,
+    /// Applications from the configuration, pinned to their outputs
+    pub apps: Vec<KioskApp>,
+    pub config: Config,
+    /// Backend specific reload, set up by [`crate::config::reload_on_sighup`]
+    pub reload: Option<ReloadFn<BackendData>>,
+    pub ipc: Option<IpcServer>,
+    pub idle: IdleState,
+    pub schedule: ScheduleState,
+    pub lockdown: LockdownState,
 }
 
 delegate_compositor!(@<BackendData: Backend + 'static> BuedchenState<BackendData>);
@@ -234,6 +269,8 @@ impl<BackendData: Backend> SeatHandler for BuedchenState<BackendData> {
         let focus = wl_surface.and_then(|s| dh.get_client(s.id()).ok());
         set_data_device_focus(dh, seat, focus.clone());
         set_primary_focus(dh, seat, focus);
+
+        self.emit_focus_changed(target);
     }
     fn cursor_image(&mut self, _seat: &Seat<Self>, image: CursorImageStatus) {
         *self.cursor_status.lock().unwrap() = image;
@@ -283,6 +320,26 @@ impl<BackendData: Backend> KeyboardShortcutsInhibitHandler for BuedchenState<Bac
 
 delegate_keyboard_shortcuts_inhibit!(@<BackendData: Backend + 'static> BuedchenState<BackendData>);
 
+impl<BackendData: Backend + 'static> IdleNotifyHandler for BuedchenState<BackendData> {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState {
+        &mut self.idle_notifier_state
+    }
+
+    fn idle_notification_created(&mut self) {
+        self.arm_idle_timer();
+    }
+}
+
+delegate_idle_notify!(@<BackendData: Backend + 'static> BuedchenState<BackendData>);
+
+impl<BackendData: Backend> IdleInhibitHandler for BuedchenState<BackendData> {
+    fn idle_inhibit_state(&mut self) -> &mut IdleInhibitManagerState {
+        &mut self.idle_inhibit_state
+    }
+}
+
+delegate_idle_inhibit!(@<BackendData: Backend + 'static> BuedchenState<BackendData>);
+
 delegate_virtual_keyboard_manager!(@<BackendData: Backend + 'static> BuedchenState<BackendData>);
 
 delegate_pointer_gestures!(@<BackendData: Backend + 'static> BuedchenState<BackendData>);
@@ -482,6 +539,8 @@ impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
         handle: LoopHandle<'static, CalloopData<BackendData>>,
         backend_data: BackendData,
         listen_on_socket: bool,
+        client: ClientSupervisor,
+        config: Config,
     ) -> BuedchenState<BackendData> {
         let dh = display.handle();
 
@@ -520,6 +579,22 @@ impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
             )
             .expect("Failed to init wayland server source");
 
+        // init the control socket next to the wayland socket
+        let ipc = socket_name
+            .as_deref()
+            .and_then(ipc::socket_path)
+            .and_then(|path| match IpcServer::bind(&handle, path) {
+                Ok(ipc) => {
+                    info!(path = ?ipc.path(), "Listening on control socket");
+                    std::env::set_var(ipc::SOCKET_ENV, ipc.path());
+                    Some(ipc)
+                }
+                Err(err) => {
+                    warn!("Failed to create the control socket: {}", err);
+                    None
+                }
+            });
+
         // init globals
         let compositor_state = CompositorState::new::<Self>(&dh);
         let data_device_state = DataDeviceState::new::<Self>(&dh);
@@ -547,11 +622,7 @@ impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
         if BackendData::HAS_GESTURES {
             PointerGesturesState::new::<Self>(&dh);
         }
-        SecurityContextState::new::<Self, _>(&dh, |client| {
-            client
-                .get_data::<ClientState>()
-                .map_or(true, |client_state| client_state.security_context.is_none())
-        });
+        SecurityContextState::new::<Self, _>(&dh, is_privileged);
 
         // init input
         let seat_name = backend_data.seat_name();
@@ -559,8 +630,12 @@ impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
 
         let cursor_status = Arc::new(Mutex::new(CursorImageStatus::default_named()));
         let pointer = seat.add_pointer();
-        seat.add_keyboard(XkbConfig::default(), 200, 25)
-            .expect("Failed to initialize the keyboard");
+        seat.add_keyboard(
+            config.keyboard.xkb_config(0),
+            config.keyboard.repeat_delay.into(),
+            config.keyboard.repeat_rate.into(),
+        )
+        .expect("Failed to initialize the keyboard");
 
         let cursor_status2 = cursor_status.clone();
         seat.tablet_seat()
@@ -571,6 +646,8 @@ impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
         let touch = seat.add_touch();
 
         let keyboard_shortcuts_inhibit_state = KeyboardShortcutsInhibitState::new::<Self>(&dh);
+        let idle_notifier_state = IdleNotifierState::new::<Self>(&dh);
+        let idle_inhibit_state = IdleInhibitManagerState::new::<Self>(&dh);
 
         BuedchenState {
             backend_data,
@@ -595,8 +672,11 @@ impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
             xdg_shell_state,
             presentation_state,
             fractional_scale_manager_state,
+            idle_notifier_state,
+            idle_inhibit_state,
             dnd_icon: None,
             suppressed_keys: Vec::new(),
+            keyboard_layout: 0,
             cursor_status,
             seat_name,
             seat,
@@ -606,6 +686,22 @@ impl<BackendData: Backend + 'static> BuedchenState<BackendData> {
             renderdoc: renderdoc::RenderDoc::new().ok(),
             show_window_preview: false,
             touch,
+            apps: config
+                .apps
+                .iter()
+                .map(|app| KioskApp {
+                    output: app.output.clone(),
+                    app_id: app.app_id.clone(),
+                    supervisor: client.with_command(app.command.clone(), app.on_give_up),
+                })
+                .collect(),
+            client,
+            config,
+            reload: None,
+            ipc,
+            idle: IdleState::default(),
+            schedule: ScheduleState::default(),
+            lockdown: LockdownState::default(),
         }
     }
 }
@@ -737,4 +833,31 @@ pub trait Backend {
     fn seat_name(&self) -> String;
     fn reset_buffers(&mut self, output: &Output);
     fn early_import(&mut self, surface: &WlSurface);
+    /// Turn the display of an output on or off, returns `false` if the backend can't
+    fn set_output_power(&mut self, _output: &Output, _on: bool) -> bool {
+        false
+    }
+    fn is_output_powered(&self, _output: &Output) -> bool {
+        true
+    }
+    /// Colour adjustment of an output, `None` if the backend can't adjust its colours
+    fn color_adjustment(&self, _output: &Output) -> Option<ColorAdjustment> {
+        None
+    }
+    /// Change the colour adjustment of an output until the configuration is reloaded
+    fn set_color_adjustment(&mut self, _output: &Output, _adjustment: ColorAdjustment) -> bool {
+        false
+    }
+    /// Turn all outputs off while idle, or on again, independent of their own power state
+    fn set_blanked(&mut self, _blanked: bool) {}
+    /// Darken all outputs by an overlay of the given opacity, `0.0` stops dimming
+    fn set_dim(&mut self, _level: f32) {}
+    /// Render the current content of an output, without the cursor
+    fn render_screenshot(
+        &mut self,
+        output: &Output,
+        space: &Space<WindowElement>,
+        show_window_preview: bool,
+        colors: &ColorConfig,
+    ) -> Result<Screenshot, Box<dyn std::error::Error>>;
 }
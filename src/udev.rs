@@ -8,14 +8,34 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::client::run_client;
+use crate::client::ClientSupervisor;
+use crate::config::{
+    reload_on_sighup, AccelProfile, ColorAdjustment, ColorConfig, Config, DeviceId, DeviceType,
+    InputRule, MirrorScaling, ModeSelection, OutputIdentity, OutputSettings, ReloadReport,
+    ScrollMethod, SendEvents,
+};
+use crate::delegate_gamma_control;
+use crate::delegate_output_management;
+use crate::delegate_output_power;
+use crate::delegate_screencopy;
+use crate::gamma_control::{color_ramps, GammaControlHandler, GammaControlManagerState};
+use crate::ipc::Event;
+use crate::output_management::{
+    HeadConfiguration, OutputHead, OutputHeadMode, OutputManagementHandler, OutputManagementState,
+};
+use crate::output_power::{OutputPowerHandler, OutputPowerManagerState};
+use crate::screencopy::{Screencopy, ScreencopyHandler, ScreencopyManagerState};
+use crate::screenshot::{render_screenshot, screenshot_on_sigusr1, Screenshot};
 
 use crate::state::SurfaceDmabufFeedback;
 use crate::{
     drawing::*,
     render::*,
-    shell::WindowElement,
-    state::{post_repaint, take_presentation_feedback, Backend, BuedchenState, CalloopData},
+    shell::{FullscreenSurface, OutputPosition, WindowElement, WindowRenderElement},
+    state::{
+        is_privileged, post_repaint, take_presentation_feedback, Backend, BuedchenState,
+        CalloopData,
+    },
 };
 use smithay::backend::renderer::ImportEgl;
 use smithay::{
@@ -30,16 +50,20 @@ use smithay::{
             DrmEvent, DrmEventMetadata, DrmNode, DrmSurface, GbmBufferedSurface, NodeType,
         },
         egl::{self, context::ContextPriority, EGLDevice, EGLDisplay},
+        input::InputEvent,
         libinput::{LibinputInputBackend, LibinputSessionInterface},
         renderer::{
             damage::{Error as OutputDamageTrackerError, OutputDamageTracker},
             element::{
-                texture::TextureBuffer, AsRenderElements, RenderElement, RenderElementStates,
+                solid::{SolidColorBuffer, SolidColorRenderElement},
+                texture::TextureBuffer,
+                AsRenderElements, Kind, RenderElement, RenderElementStates,
             },
             gles::{GlesRenderer, GlesTexture},
             multigpu::{gbm::GbmGlesBackend, GpuManager, MultiRenderer, MultiTexture},
             sync::SyncPoint,
             Bind, DebugFlags, ExportMem, ImportDma, ImportMemWl, Offscreen, Renderer,
+            TextureMapping,
         },
         session::{
             libseat::{self, LibSeatSession},
@@ -50,11 +74,12 @@ use smithay::{
     },
     delegate_dmabuf, delegate_drm_lease,
     desktop::{
+        layer_map_for_output,
         space::{Space, SurfaceTree},
         utils::OutputPresentationFeedback,
     },
     input::pointer::{CursorImageAttributes, CursorImageStatus},
-    output::{Mode as WlMode, Output, PhysicalProperties, Subpixel},
+    output::{Mode as WlMode, Output, PhysicalProperties, Scale as OutputScale, Subpixel},
     reexports::{
         calloop::{
             timer::{TimeoutAction, Timer},
@@ -62,10 +87,10 @@ use smithay::{
         },
         drm::{
             self,
-            control::{connector, crtc, Device, ModeTypeFlags},
+            control::{connector, crtc, Device, Mode as DrmMode, ModeTypeFlags},
             Device as _,
         },
-        input::Libinput,
+        input::{self as libinput, DeviceConfigResult, Libinput},
         rustix::fs::OFlags,
         wayland_protocols::wp::{
             linux_dmabuf::zv1::server::zwp_linux_dmabuf_feedback_v1,
@@ -79,13 +104,14 @@ use smithay::{
     wayland::{
         compositor,
         dmabuf::{
-            DmabufFeedback, DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState,
-            ImportError,
+            get_dmabuf, DmabufFeedback, DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler,
+            DmabufState, ImportError,
         },
         drm_lease::{
             DrmLease, DrmLeaseBuilder, DrmLeaseHandler, DrmLeaseRequest, DrmLeaseState,
             LeaseRejected,
         },
+        shm::with_buffer_contents_mut,
     },
 };
 use smithay_drm_extras::{
@@ -120,6 +146,7 @@ struct UdevOutputId {
 pub struct UdevData {
     pub session: LibSeatSession,
     dh: DisplayHandle,
+    handle: LoopHandle<'static, CalloopData<UdevData>>,
     dmabuf_state: Option<(DmabufState, DmabufGlobal)>,
     primary_gpu: DrmNode,
     allocator: Option<Box<dyn Allocator<Buffer = Dmabuf, Error = AnyError>>>,
@@ -131,6 +158,24 @@ pub struct UdevData {
     fps_texture: Option<MultiTexture>,
     pointer_image: crate::cursor::Cursor,
     debug_flags: DebugFlags,
+    output_management_state: OutputManagementState,
+    /// Output settings changed at runtime through output management, by connector name
+    output_overrides: HashMap<String, OutputSettings>,
+    screencopy_state: ScreencopyManagerState,
+    output_power_state: OutputPowerManagerState,
+    gamma_control_state: GammaControlManagerState,
+    /// Colour adjustments changed at runtime through the control socket, by connector name
+    color_overrides: HashMap<String, ColorAdjustment>,
+    /// Connected libinput devices, configured again when the rules change
+    input_devices: Vec<libinput::Device>,
+    /// Where the absolute positions of each input device end up, by device id
+    ///
+    /// Resolved again whenever devices, outputs or the configuration change.
+    input_targets: HashMap<String, Option<InputTarget>>,
+    /// Outputs are turned off because the seat is idle
+    blanked: bool,
+    /// Opacity of the overlay darkening all outputs
+    dim: f32,
 }
 
 impl UdevData {
@@ -149,6 +194,52 @@ impl UdevData {
     pub fn debug_flags(&self) -> DebugFlags {
         self.debug_flags
     }
+
+    /// Program the gamma ramps of an output, returns `false` if that failed
+    fn update_gamma(&self, output: &Output) -> bool {
+        let Some(id) = output.user_data().get::<UdevOutputId>() else {
+            return false;
+        };
+        let Some(device) = self.backends.get(&id.device_id) else {
+            return false;
+        };
+        let Some(surface) = device.surfaces.get(&id.crtc) else {
+            return false;
+        };
+        match set_gamma_ramps(&device.drm, id.crtc, surface) {
+            Ok(()) => true,
+            Err(err) => {
+                warn!(output = output.name(), "Failed to set gamma ramps: {}", err);
+                false
+            }
+        }
+    }
+
+    fn apply_color_adjustment(&mut self, output: &Output, adjustment: ColorAdjustment) -> bool {
+        let Some(surface) = output.user_data().get::<UdevOutputId>().and_then(|id| {
+            self.backends
+                .get_mut(&id.device_id)?
+                .surfaces
+                .get_mut(&id.crtc)
+        }) else {
+            return false;
+        };
+
+        info!(
+            output = output.name(),
+            ?adjustment,
+            "Changing colour adjustment"
+        );
+        surface.color = adjustment;
+        // ramps of a gamma control client stay in place until it lets go of the output
+        surface.client_gamma.is_some() || self.update_gamma(output)
+    }
+
+    /// Start rendering a device again after its outputs were turned off, only `crtc` if given
+    fn schedule_repaint(&self, node: DrmNode, crtc: Option<crtc::Handle>) {
+        self.handle
+            .insert_idle(move |data| data.state.render(node, crtc));
+    }
 }
 
 impl DmabufHandler for BuedchenState<UdevData> {
@@ -171,6 +262,169 @@ impl DmabufHandler for BuedchenState<UdevData> {
 }
 delegate_dmabuf!(BuedchenState<UdevData>);
 
+impl OutputManagementHandler for BuedchenState<UdevData> {
+    fn output_management_state(&mut self) -> &mut OutputManagementState {
+        &mut self.backend_data.output_management_state
+    }
+
+    fn apply_output_configuration(
+        &mut self,
+        configuration: Vec<HeadConfiguration>,
+        test_only: bool,
+    ) -> bool {
+        let heads = self.output_heads();
+
+        let mut overrides = Vec::new();
+        for head_config in configuration {
+            let Some((identity, head)) =
+                heads.iter().find(|(_, head)| head.name == head_config.name)
+            else {
+                warn!(
+                    output = head_config.name,
+                    "Unknown output in output configuration"
+                );
+                return false;
+            };
+            if head_config.custom_mode.is_some() {
+                warn!(output = head.name, "Custom modes are not supported");
+                return false;
+            }
+
+            let current =
+                output_settings(&self.config, &self.backend_data.output_overrides, identity);
+            let mode = match head_config.mode {
+                Some((size, refresh)) => {
+                    if !head
+                        .modes
+                        .iter()
+                        .any(|mode| mode.size == size && mode.refresh == refresh)
+                    {
+                        warn!(output = head.name, ?size, refresh, "Unknown mode");
+                        return false;
+                    }
+                    ModeSelection::Exact {
+                        width: size.w as u16,
+                        height: size.h as u16,
+                        refresh: Some(refresh as u32),
+                    }
+                }
+                None => current.mode,
+            };
+            let settings = OutputSettings {
+                enabled: head_config.enabled,
+                mode,
+                transform: head_config.transform.unwrap_or(current.transform),
+                scale: head_config.scale.or(current.scale),
+                position: head_config.position.or(current.position),
+                mirror: current.mirror,
+                mirror_scaling: current.mirror_scaling,
+            };
+            overrides.push((head.name.clone(), settings));
+        }
+
+        let mut output_overrides = self.backend_data.output_overrides.clone();
+        output_overrides.extend(overrides);
+        if !self.test_output_config(&output_overrides) {
+            return false;
+        }
+        if test_only {
+            return true;
+        }
+
+        let previous = std::mem::replace(&mut self.backend_data.output_overrides, output_overrides);
+        if self.apply_output_config() {
+            return true;
+        }
+        warn!("Failed to apply the output configuration, going back to the previous one");
+        self.backend_data.output_overrides = previous;
+        self.apply_output_config();
+        false
+    }
+}
+delegate_output_management!(BuedchenState<UdevData>);
+
+impl ScreencopyHandler for BuedchenState<UdevData> {
+    fn frame(&mut self, screencopy: Screencopy) {
+        // frames are copied on the next render of the output, dropping them fails the copy
+        let surface = screencopy
+            .output()
+            .user_data()
+            .get::<UdevOutputId>()
+            .and_then(|id| {
+                self.backend_data
+                    .backends
+                    .get_mut(&id.device_id)?
+                    .surfaces
+                    .get_mut(&id.crtc)
+            });
+        // an output that is off won't render, so the copy fails right away
+        if let Some(surface) = surface.filter(|surface| surface.powered) {
+            if !self.backend_data.blanked {
+                surface.screencopies.push(screencopy);
+            }
+        }
+    }
+}
+delegate_screencopy!(BuedchenState<UdevData>);
+
+impl OutputPowerHandler for BuedchenState<UdevData> {
+    fn output_power_state(&mut self) -> &mut OutputPowerManagerState {
+        &mut self.backend_data.output_power_state
+    }
+
+    fn output_powered(&mut self, output: &Output) -> bool {
+        // blanking is not a power mode the clients could change
+        output
+            .user_data()
+            .get::<UdevOutputId>()
+            .and_then(|id| {
+                self.backend_data
+                    .backends
+                    .get(&id.device_id)?
+                    .surfaces
+                    .get(&id.crtc)
+            })
+            .map_or(true, |surface| surface.powered)
+    }
+
+    fn set_output_powered(&mut self, output: &Output, on: bool) -> bool {
+        self.backend_data.set_output_power(output, on)
+    }
+}
+delegate_output_power!(BuedchenState<UdevData>);
+
+impl GammaControlHandler for BuedchenState<UdevData> {
+    fn gamma_control_state(&mut self) -> &mut GammaControlManagerState {
+        &mut self.backend_data.gamma_control_state
+    }
+
+    fn gamma_size(&mut self, output: &Output) -> Option<u32> {
+        let id = output.user_data().get::<UdevOutputId>()?;
+        let device = self.backend_data.backends.get(&id.device_id)?;
+        device
+            .drm
+            .get_crtc(id.crtc)
+            .ok()
+            .map(|info| info.gamma_length())
+            .filter(|size| *size > 0)
+    }
+
+    fn set_gamma(&mut self, output: &Output, ramps: Option<Vec<u16>>) -> bool {
+        let Some(surface) = output.user_data().get::<UdevOutputId>().and_then(|id| {
+            self.backend_data
+                .backends
+                .get_mut(&id.device_id)?
+                .surfaces
+                .get_mut(&id.crtc)
+        }) else {
+            return false;
+        };
+        surface.client_gamma = ramps;
+        self.backend_data.update_gamma(output)
+    }
+}
+delegate_gamma_control!(BuedchenState<UdevData>);
+
 impl Backend for UdevData {
     const HAS_RELATIVE_MOTION: bool = true;
     const HAS_GESTURES: bool = true;
@@ -197,9 +451,126 @@ impl Backend for UdevData {
             warn!("Early buffer import failed: {}", err);
         }
     }
+
+    fn set_output_power(&mut self, output: &Output, on: bool) -> bool {
+        let Some(id) = output.user_data().get::<UdevOutputId>() else {
+            return false;
+        };
+        let Some(surface) = self
+            .backends
+            .get_mut(&id.device_id)
+            .and_then(|backend| backend.surfaces.get_mut(&id.crtc))
+        else {
+            return false;
+        };
+        if surface.powered == on {
+            return true;
+        }
+
+        info!(output = output.name(), on, "Changing output power");
+        surface.powered = on;
+        if on {
+            // the next frame is a full repaint enabling the crtc again
+            surface.compositor.reset_buffers();
+            if !self.blanked {
+                self.schedule_repaint(id.device_id, Some(id.crtc));
+            }
+        } else {
+            surface.screencopies.clear();
+            if let Err(err) = surface.compositor.clear() {
+                warn!("Failed to power off output: {}", err);
+            }
+        }
+        self.output_power_state.mode_changed(output, on);
+        true
+    }
+
+    fn is_output_powered(&self, output: &Output) -> bool {
+        output
+            .user_data()
+            .get::<UdevOutputId>()
+            .and_then(|id| self.backends.get(&id.device_id)?.surfaces.get(&id.crtc))
+            .map_or(true, |surface| surface.powered && !self.blanked)
+    }
+
+    fn color_adjustment(&self, output: &Output) -> Option<ColorAdjustment> {
+        output
+            .user_data()
+            .get::<UdevOutputId>()
+            .and_then(|id| self.backends.get(&id.device_id)?.surfaces.get(&id.crtc))
+            .map(|surface| surface.color)
+    }
+
+    fn set_color_adjustment(&mut self, output: &Output, adjustment: ColorAdjustment) -> bool {
+        self.color_overrides.insert(output.name(), adjustment);
+        self.apply_color_adjustment(output, adjustment)
+    }
+
+    fn set_blanked(&mut self, blanked: bool) {
+        if self.blanked == blanked {
+            return;
+        }
+
+        info!(blanked, "Blanking outputs");
+        self.blanked = blanked;
+        for surface in self
+            .backends
+            .values_mut()
+            .flat_map(|backend| backend.surfaces.values_mut())
+            .filter(|surface| surface.powered)
+        {
+            if !blanked {
+                surface.compositor.reset_buffers();
+            } else {
+                surface.screencopies.clear();
+                if let Err(err) = surface.compositor.clear() {
+                    warn!("Failed to blank output: {}", err);
+                }
+            }
+        }
+        if !blanked {
+            for node in self.backends.keys() {
+                self.schedule_repaint(*node, None);
+            }
+        }
+    }
+
+    fn set_dim(&mut self, level: f32) {
+        self.dim = level;
+        for surface in self
+            .backends
+            .values_mut()
+            .flat_map(|backend| backend.surfaces.values_mut())
+        {
+            surface.dim = level;
+        }
+    }
+
+    fn render_screenshot(
+        &mut self,
+        output: &Output,
+        space: &Space<WindowElement>,
+        show_window_preview: bool,
+        colors: &ColorConfig,
+    ) -> Result<Screenshot, Box<dyn std::error::Error>> {
+        let render_node = output
+            .user_data()
+            .get::<UdevOutputId>()
+            .and_then(|id| self.backends.get(&id.device_id))
+            .map(|backend| backend.render_node)
+            .ok_or("output is not driven by this backend")?;
+        let mut renderer = self.gpus.single_renderer(&render_node)?;
+        render_screenshot::<_, GlesTexture>(
+            &mut renderer,
+            output,
+            space,
+            show_window_preview,
+            colors,
+        )
+    }
 }
 
-pub fn run_udev(executable: &[String]) {
+pub fn run_udev(client: ClientSupervisor, config: Config) {
     let mut event_loop = EventLoop::try_new().unwrap();
     let display = Display::new().unwrap();
     let mut display_handle = display.handle();
@@ -244,20 +615,43 @@ pub fn run_udev(executable: &[String]) {
 
     let data = UdevData {
         dh: display_handle.clone(),
+        handle: event_loop.handle(),
         dmabuf_state: None,
         session,
         primary_gpu,
         gpus,
         allocator: None,
         backends: HashMap::new(),
-        pointer_image: crate::cursor::Cursor::load(),
+        pointer_image: crate::cursor::Cursor::load(&config.cursor),
         pointer_images: Vec::new(),
         pointer_element: PointerElement::default(),
         #[cfg(feature = "debug")]
         fps_texture: None,
         debug_flags: DebugFlags::empty(),
+        output_management_state: OutputManagementState::new::<BuedchenState<UdevData>, _>(
+            &display_handle,
+            is_privileged,
+        ),
+        output_overrides: HashMap::new(),
+        screencopy_state: ScreencopyManagerState::new::<BuedchenState<UdevData>, _>(
+            &display_handle,
+            is_privileged,
+        ),
+        output_power_state: OutputPowerManagerState::new::<BuedchenState<UdevData>, _>(
+            &display_handle,
+            is_privileged,
+        ),
+        gamma_control_state: GammaControlManagerState::new::<BuedchenState<UdevData>, _>(
+            &display_handle,
+            is_privileged,
+        ),
+        color_overrides: HashMap::new(),
+        input_devices: Vec::new(),
+        input_targets: HashMap::new(),
+        blanked: false,
+        dim: 0.0,
     };
-    let mut state = BuedchenState::init(display, event_loop.handle(), data, true);
+    let mut state = BuedchenState::init(display, event_loop.handle(), data, true, client, config);
 
     /*
      * Initialize the udev backend
@@ -284,8 +678,23 @@ pub fn run_udev(executable: &[String]) {
      */
     event_loop
         .handle()
-        .insert_source(libinput_backend, move |event, _, data| {
+        .insert_source(libinput_backend, move |mut event, _, data| {
             let dh = data.state.backend_data.dh.clone();
+            match &mut event {
+                InputEvent::DeviceAdded { device } => {
+                    configure_input_device(&data.state.config.inputs, device);
+                    data.state.backend_data.input_devices.push(device.clone());
+                    data.state.map_input_devices();
+                }
+                InputEvent::DeviceRemoved { device } => {
+                    data.state
+                        .backend_data
+                        .input_devices
+                        .retain(|known| known != device);
+                    data.state.map_input_devices();
+                }
+                _ => {}
+            }
             data.state.process_input_event(&dh, event)
         })
         .unwrap();
@@ -323,10 +732,16 @@ pub fn run_udev(executable: &[String]) {
                     if let Some(lease_global) = backend.leasing_global.as_mut() {
                         lease_global.resume::<BuedchenState<UdevData>>();
                     }
-                    for surface in backend.surfaces.values_mut() {
+                    for (crtc, surface) in backend.surfaces.iter_mut() {
                         if let Err(err) = surface.compositor.surface().reset_state() {
                             warn!("Failed to reset drm surface state: {}", err);
                         }
+                        // whoever had the device in the meantime might have changed the ramps
+                        if surface.has_custom_gamma() {
+                            if let Err(err) = set_gamma_ramps(&backend.drm, *crtc, surface) {
+                                warn!("Failed to restore gamma ramps: {}", err);
+                            }
+                        }
                         // reset the buffers after resume to trigger a full redraw
                         // this is important after a vt switch as the primary plane
                         // has no content and damage tracking may prevent a redraw
@@ -347,15 +762,6 @@ pub fn run_udev(executable: &[String]) {
             error!("Skipping device {device_id}: {err}");
         }
     }
-    state.shm_state.update_formats(
-        state
-            .backend_data
-            .gpus
-            .single_renderer(&primary_gpu)
-            .unwrap()
-            .shm_formats(),
-    );
-
     if state.backend_data.allocator.is_none() {
         info!("No vulkan allocator found, using GBM.");
         let gbm = state
@@ -374,11 +780,18 @@ pub fn run_udev(executable: &[String]) {
         });
     }
 
-    let mut renderer = state
-        .backend_data
-        .gpus
-        .single_renderer(&primary_gpu)
-        .unwrap();
+    // there is no software renderer to fall back to, so give up instead of panicking later on
+    let mut renderer = match state.backend_data.gpus.single_renderer(&primary_gpu) {
+        Ok(renderer) => renderer,
+        Err(err) => {
+            error!(
+                ?primary_gpu,
+                "Failed to initialize GLES rendering on the primary gpu: {}", err
+            );
+            return;
+        }
+    };
+    state.shm_state.update_formats(renderer.shm_formats());
 
     #[cfg(feature = "debug")]
     {
@@ -469,21 +882,20 @@ pub fn run_udev(executable: &[String]) {
         })
         .unwrap();
 
-    let socket_name = match &state.socket_name {
-        None => {
-            error!("WAYLAND_DISPLAY was not set yet by compositor");
-            return;
-        }
-        Some(socket_name) => socket_name,
-    };
+    if let Err(err) = reload_on_sighup(&mut state, BuedchenState::reload_udev_config) {
+        warn!("Unable to reload the configuration on SIGHUP: {}", err);
+    }
+    if let Err(err) = screenshot_on_sigusr1(&event_loop.handle()) {
+        warn!("Unable to take screenshots on SIGUSR1: {}", err);
+    }
+    state.arm_idle_timer();
+    // before the client is started, it might not be supposed to run yet
+    state.apply_schedule();
 
-    let client_join_handle = match run_client(executable, &socket_name) {
-        Ok(join_handle) => join_handle,
-        Err(e) => {
-            error!("Couldn't start client: {}", e);
-            return;
-        }
-    };
+    if let Err(e) = state.start_client() {
+        error!("Couldn't start client: {}", e);
+        return;
+    }
     info!("Client started successfully");
 
     /*
@@ -509,26 +921,7 @@ pub fn run_udev(executable: &[String]) {
             display_handle.flush_clients().unwrap();
         }
 
-        if client_join_handle.is_finished() {
-            info!("client has finished. stopping...");
-            match client_join_handle.join() {
-                Ok(client_exit) => match client_exit {
-                    Ok(exit_code) => {
-                        if exit_code.success() {
-                            info!("client exited normally");
-                        } else {
-                            error!("client exited abnormally with code: {}", exit_code);
-                        }
-                    }
-                    Err(e) => error!(
-                        "client exited abnormally and we couldn't get an exit code: {:?}",
-                        e
-                    ),
-                },
-                Err(e) => error!("Couldn't join client thread: {:?}", e),
-            }
-            break;
-        }
+        state.check_client();
     }
 }
 
@@ -658,6 +1051,17 @@ impl SurfaceComposition {
         }
     }
 
+    /// Disable the crtc, the next queued frame enables it again
+    fn clear(&mut self) -> Result<(), SwapBuffersError> {
+        match self {
+            SurfaceComposition::Compositor(c) => c.clear().map_err(Into::<SwapBuffersError>::into),
+            SurfaceComposition::Surface { surface, .. } => surface
+                .surface()
+                .clear_state()
+                .map_err(Into::<SwapBuffersError>::into),
+        }
+    }
+
     #[profiling::function]
     fn queue_frame(
         &mut self,
@@ -740,6 +1144,17 @@ impl SurfaceComposition {
         }
     }
 
+    fn use_mode(&mut self, mode: DrmMode) -> Result<(), SwapBuffersError> {
+        match self {
+            SurfaceComposition::Compositor(c) => {
+                c.use_mode(mode).map_err(Into::<SwapBuffersError>::into)
+            }
+            SurfaceComposition::Surface { surface, .. } => surface
+                .use_mode(mode)
+                .map_err(Into::<SwapBuffersError>::into),
+        }
+    }
+
     fn set_debug_flags(&mut self, flags: DebugFlags) {
         match self {
             SurfaceComposition::Surface {
@@ -771,6 +1186,59 @@ struct SurfaceData {
     #[cfg(feature = "debug")]
     fps_element: Option<FpsElement<MultiTexture>>,
     dmabuf_feedback: Option<DrmSurfaceDmabufFeedback>,
+    mirror: Option<Mirror>,
+    /// Whether the crtc is enabled, nothing is rendered while the output is powered off
+    powered: bool,
+    /// Screencopy frames waiting for the next render
+    screencopies: Vec<Screencopy>,
+    /// Damage since the last damage-tracked screencopy
+    screencopy_damage: Option<OutputDamageTracker>,
+    /// Opacity of the overlay darkening the output
+    dim: f32,
+    dim_buffer: SolidColorBuffer,
+    color: ColorAdjustment,
+    /// Gamma ramps of a gamma control client, used instead of the colour adjustment
+    client_gamma: Option<Vec<u16>>,
+}
+
+impl SurfaceData {
+    /// Whether the gamma ramps differ from the ones the output started with
+    fn has_custom_gamma(&self) -> bool {
+        self.client_gamma.is_some() || self.color != ColorAdjustment::default()
+    }
+}
+
+/// Where the absolute positions of an input device end up
+#[derive(Debug, Clone)]
+pub enum InputTarget {
+    Output(Output),
+    /// A mirror output, showing `source` scaled as configured
+    Mirror {
+        output: Output,
+        source: Output,
+        scaling: MirrorScaling,
+    },
+    /// The bounding box of all outputs, for devices not belonging to a single one
+    Layout,
+}
+
+impl InputTarget {
+    /// Output the device is mounted on
+    fn output(&self) -> Option<&Output> {
+        match self {
+            InputTarget::Output(output) | InputTarget::Mirror { output, .. } => Some(output),
+            InputTarget::Layout => None,
+        }
+    }
+}
+
+/// An output showing the content of another output instead of its own part of the space
+struct Mirror {
+    output: Output,
+    /// Connector name of the mirrored output
+    source: String,
+    scaling: MirrorScaling,
+    settings: OutputSettings,
 }
 
 impl Drop for SurfaceData {
@@ -803,6 +1271,8 @@ enum DeviceAddError {
     GbmDevice(std::io::Error),
     #[error("Failed to access drm node: {0}")]
     DrmNode(CreateDrmNodeError),
+    #[error("Failed to initialize EGL on the device: {0}")]
+    EglDisplay(egl::Error),
     #[error("Failed to add device to GpuManager: {0}")]
     AddNode(egl::Error),
 }
@@ -888,6 +1358,13 @@ impl BuedchenState<UdevData> {
             DrmDevice::new(fd.clone(), true).map_err(DeviceAddError::DrmDevice)?;
         let gbm = GbmDevice::new(fd).map_err(DeviceAddError::GbmDevice)?;
 
+        let render_node = EGLDevice::device_for_display(
+            &EGLDisplay::new(gbm.clone()).map_err(DeviceAddError::EglDisplay)?,
+        )
+        .ok()
+        .and_then(|x| x.try_get_render_node().ok().flatten())
+        .unwrap_or(node);
+
         let registration_token = self
             .handle
             .insert_source(
@@ -904,11 +1381,6 @@ impl BuedchenState<UdevData> {
             )
             .unwrap();
 
-        let render_node = EGLDevice::device_for_display(&EGLDisplay::new(gbm.clone()).unwrap())
-            .ok()
-            .and_then(|x| x.try_get_render_node().ok().flatten())
-            .unwrap_or(node);
-
         self.backend_data
             .gpus
             .as_mut()
@@ -944,92 +1416,569 @@ impl BuedchenState<UdevData> {
         Ok(())
     }
 
-    fn connector_connected(
-        &mut self,
-        node: DrmNode,
-        connector: connector::Info,
-        crtc: crtc::Handle,
-    ) {
-        let device = if let Some(device) = self.backend_data.backends.get_mut(&node) {
-            device
-        } else {
-            return;
-        };
+    fn reload_udev_config(&mut self) -> Result<ReloadReport, String> {
+        let (previous, report) = self.reload_config()?;
 
-        let mut renderer = self
-            .backend_data
-            .gpus
-            .single_renderer(&device.render_node)
-            .unwrap();
-        let render_formats = renderer
-            .as_mut()
-            .egl_context()
-            .dmabuf_render_formats()
-            .clone();
+        if previous.cursor != self.config.cursor {
+            self.backend_data.pointer_image = crate::cursor::Cursor::load(&self.config.cursor);
+            self.backend_data.pointer_images.clear();
+        }
 
-        let output_name = format!(
-            "{}-{}",
-            connector.interface().as_str(),
-            connector.interface_id()
-        );
-        info!(?crtc, "Trying to setup connector {}", output_name,);
+        // a reload makes the configuration file authoritative again
+        let had_overrides = !self.backend_data.output_overrides.is_empty();
+        self.backend_data.output_overrides.clear();
+        if had_overrides
+            || previous.output != self.config.output
+            || previous.outputs != self.config.outputs
+        {
+            self.apply_output_config();
+        }
 
-        let non_desktop = device
-            .drm
-            .get_properties(connector.handle())
-            .ok()
-            .and_then(|props| {
-                let (info, value) = props
-                    .into_iter()
-                    .filter_map(|(handle, value)| {
-                        let info = device.drm.get_property(handle).ok()?;
+        self.backend_data.color_overrides.clear();
+        self.apply_color_config();
 
-                        Some((info, value))
-                    })
-                    .find(|(info, _)| info.name().to_str() == Ok("non-desktop"))?;
+        if previous.inputs != self.config.inputs {
+            for device in self.backend_data.input_devices.iter_mut() {
+                configure_input_device(&self.config.inputs, device);
+            }
+            self.map_input_devices();
+        }
 
-                info.value_type().convert_value(value).as_boolean()
-            })
-            .unwrap_or(false);
+        if previous.schedule != self.config.schedule {
+            self.apply_schedule();
+        }
 
-        let (make, model) = EdidInfo::for_connector(&device.drm, connector.handle())
-            .map(|info| (info.manufacturer, info.model))
-            .unwrap_or_else(|| ("Unknown".into(), "Unknown".into()));
+        if previous.idle != self.config.idle {
+            // start over with the new timeouts
+            self.notify_activity();
+            self.arm_idle_timer();
+        }
 
-        if non_desktop {
-            info!(
-                "Connector {} is non-desktop, setting up for leasing",
-                output_name
-            );
-            device
-                .non_desktop_connectors
-                .push((connector.handle(), crtc));
-            if let Some(lease_state) = device.leasing_global.as_mut() {
-                lease_state.add_connector::<BuedchenState<UdevData>>(
-                    connector.handle(),
-                    output_name,
-                    format!("{} {}", make, model),
-                );
-            }
-        } else {
-            let mode_id = connector
-                .modes()
-                .iter()
-                .position(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
-                .unwrap_or(0);
+        Ok(report)
+    }
 
-            let drm_mode = connector.modes()[mode_id];
-            let wl_mode = WlMode::from(drm_mode);
+    /// Where the absolute positions of the input device with the given id end up
+    ///
+    /// Devices not known yet span all outputs.
+    pub fn input_target(&self, id: &str) -> Option<InputTarget> {
+        match self.backend_data.input_targets.get(id) {
+            Some(target) => target.clone(),
+            None => Some(InputTarget::Layout),
+        }
+    }
 
-            let surface = match device
-                .drm
-                .create_surface(crtc, drm_mode, &[connector.handle()])
-            {
-                Ok(surface) => surface,
-                Err(err) => {
-                    warn!("Failed to create drm surface: {}", err);
-                    return;
-                }
+    /// Resolve the output of every input device again
+    fn map_input_devices(&mut self) {
+        let targets = self
+            .backend_data
+            .input_devices
+            .iter()
+            .map(|device| {
+                let target = self.resolve_input_target(device);
+                debug!(
+                    device = device.sysname(),
+                    output = ?target.as_ref().and_then(InputTarget::output).map(Output::name),
+                    "Mapping input device"
+                );
+                (device.sysname().to_owned(), target)
+            })
+            .collect();
+        self.backend_data.input_targets = targets;
+    }
+
+    /// Output the absolute positions of `device` are mapped onto, mirrors included
+    ///
+    /// Taken from the input rules, the udev `WL_OUTPUT` property of the device, the EDID model
+    /// name contained in the device name or the physical size of the panel, in this order. Devices
+    /// not matching any output span all of them.
+    fn resolve_input_target(&self, device: &libinput::Device) -> Option<InputTarget> {
+        let mirrors = self
+            .backend_data
+            .backends
+            .values()
+            .flat_map(|backend| backend.surfaces.values())
+            .filter_map(|surface| surface.mirror.as_ref())
+            .filter_map(|mirror| {
+                let source = self
+                    .space
+                    .outputs()
+                    .find(|output| output.name() == mirror.source)?;
+                Some(InputTarget::Mirror {
+                    output: mirror.output.clone(),
+                    source: source.clone(),
+                    scaling: mirror.scaling,
+                })
+            });
+        let targets = self
+            .space
+            .outputs()
+            .cloned()
+            .map(InputTarget::Output)
+            .chain(mirrors)
+            .collect::<Vec<_>>();
+
+        let configured = matching_input_rules(&self.config.inputs, device)
+            .into_iter()
+            .rev()
+            .find_map(|rule| rule.output.as_deref());
+        if let Some(name) = configured.or(device.output_name()) {
+            // don't move the input onto another output while the configured one is missing
+            return targets
+                .into_iter()
+                .find(|target| target.output().is_some_and(|output| output.name() == name));
+        }
+
+        let device_name = device.name().to_lowercase();
+        let by_model = targets.iter().filter(|target| {
+            let model = target
+                .output()
+                .and_then(|output| output.user_data().get::<OutputIdentity>())
+                .map_or(String::new(), |identity| identity.model.to_lowercase());
+            !model.is_empty() && device_name.contains(&model)
+        });
+        if let Some(target) = single(by_model) {
+            return Some(target.clone());
+        }
+
+        if let Some((width, height)) = device.size() {
+            let by_size = targets.iter().filter(|target| {
+                let Some(size) = target
+                    .output()
+                    .map(|output| output.physical_properties().size)
+                else {
+                    return false;
+                };
+                let close = |a: f64, b: i32| (a - b as f64).abs() <= b as f64 * SIZE_TOLERANCE;
+                // the panel might be mounted rotated relative to the touchscreen
+                (close(width, size.w) && close(height, size.h))
+                    || (close(width, size.h) && close(height, size.w))
+            });
+            if let Some(target) = single(by_size) {
+                return Some(target.clone());
+            }
+        }
+
+        Some(InputTarget::Layout)
+    }
+
+    /// Set the colour adjustment of all outputs to the configured one where it differs
+    fn apply_color_config(&mut self) {
+        let outputs = self
+            .space
+            .outputs()
+            .cloned()
+            .chain(
+                self.backend_data
+                    .backends
+                    .values()
+                    .flat_map(|device| device.surfaces.values())
+                    .filter_map(|surface| surface.mirror.as_ref())
+                    .map(|mirror| mirror.output.clone()),
+            )
+            .collect::<Vec<_>>();
+        for output in outputs {
+            let identity = output.user_data().get::<OutputIdentity>().unwrap();
+            let adjustment =
+                color_adjustment(&self.config, &self.backend_data.color_overrides, identity);
+            if self.backend_data.color_adjustment(&output) != Some(adjustment) {
+                self.backend_data
+                    .apply_color_adjustment(&output, adjustment);
+            }
+        }
+    }
+
+    /// Check that the outputs staying enabled support the modes `overrides` give them
+    ///
+    /// On atomic devices `use_mode` does a test-only commit of the new mode, the pending mode
+    /// is put back right after. Outputs getting enabled can only be tried by creating them.
+    fn test_output_config(&self, overrides: &HashMap<String, OutputSettings>) -> bool {
+        for device in self.backend_data.backends.values() {
+            for (connector, crtc) in device.drm_scanner.crtcs() {
+                let Some(surface) = device.surfaces.get(&crtc) else {
+                    continue;
+                };
+                let output = match &surface.mirror {
+                    Some(mirror) => Some(&mirror.output),
+                    None => self.space.outputs().find(|o| {
+                        o.user_data()
+                            .get::<UdevOutputId>()
+                            .map(|id| id.device_id == surface.device_id && id.crtc == crtc)
+                            .unwrap_or(false)
+                    }),
+                };
+                let Some(output) = output else {
+                    continue;
+                };
+
+                let identity = output.user_data().get::<OutputIdentity>().unwrap();
+                let settings = output_settings(&self.config, overrides, identity);
+                let drm_mode = connector.modes()[select_mode(connector.modes(), settings.mode)];
+                if !settings.enabled || output.current_mode() == Some(WlMode::from(drm_mode)) {
+                    continue;
+                }
+
+                let drm_surface = surface.compositor.surface();
+                let pending = drm_surface.pending_mode();
+                if let Err(err) = drm_surface.use_mode(drm_mode) {
+                    warn!(output = output.name(), "Mode is not supported: {}", err);
+                    return false;
+                }
+                if let Err(err) = drm_surface.use_mode(pending) {
+                    warn!(
+                        output = output.name(),
+                        "Failed to restore the mode: {}", err
+                    );
+                }
+            }
+        }
+        true
+    }
+
+    /// Apply the output rules to all connected outputs, enabling or disabling them as needed
+    ///
+    /// Returns `false` if the settings of some output could not be applied.
+    fn apply_output_config(&mut self) -> bool {
+        let mut success = true;
+        let mut connectors = Vec::new();
+        for (node, device) in self.backend_data.backends.iter() {
+            for (info, crtc) in device.drm_scanner.crtcs() {
+                let non_desktop = device
+                    .non_desktop_connectors
+                    .iter()
+                    .any(|(handle, _)| *handle == info.handle());
+                if !non_desktop {
+                    connectors.push((*node, info.clone(), crtc));
+                }
+            }
+        }
+
+        for (node, connector, crtc) in connectors {
+            let output = self
+                .space
+                .outputs()
+                .find(|o| {
+                    o.user_data()
+                        .get::<UdevOutputId>()
+                        .map(|id| id.device_id == node && id.crtc == crtc)
+                        .unwrap_or(false)
+                })
+                .cloned();
+            let Some(output) = output else {
+                let mirror = self
+                    .backend_data
+                    .backends
+                    .get(&node)
+                    .and_then(|device| device.surfaces.get(&crtc))
+                    .and_then(|surface| surface.mirror.as_ref())
+                    .map(|mirror| {
+                        let identity = mirror.output.user_data().get::<OutputIdentity>();
+                        (identity.unwrap().clone(), mirror.settings.clone())
+                    });
+                if let Some((identity, current)) = mirror {
+                    let settings = output_settings(
+                        &self.config,
+                        &self.backend_data.output_overrides,
+                        &identity,
+                    );
+                    if settings != current {
+                        self.connector_disconnected(node, connector.clone(), crtc);
+                        self.connector_connected(node, connector, crtc);
+                    }
+                } else {
+                    // might have been disabled before
+                    self.connector_connected(node, connector, crtc);
+                }
+                continue;
+            };
+
+            let identity = output.user_data().get::<OutputIdentity>().unwrap().clone();
+            let settings =
+                output_settings(&self.config, &self.backend_data.output_overrides, &identity);
+            if !settings.enabled {
+                info!(?identity, "Output is disabled by the configuration");
+                self.connector_disconnected(node, connector, crtc);
+                continue;
+            }
+            if settings.mirror.is_some() {
+                self.connector_disconnected(node, connector.clone(), crtc);
+                self.connector_connected(node, connector, crtc);
+                continue;
+            }
+
+            let Some(surface) = self
+                .backend_data
+                .backends
+                .get_mut(&node)
+                .and_then(|device| device.surfaces.get_mut(&crtc))
+            else {
+                continue;
+            };
+
+            let drm_mode = connector.modes()[select_mode(connector.modes(), settings.mode)];
+            let wl_mode = WlMode::from(drm_mode);
+            if output.current_mode() != Some(wl_mode) {
+                if let Err(err) = surface.compositor.use_mode(drm_mode) {
+                    warn!(output = output.name(), "Failed to change mode: {}", err);
+                    success = false;
+                    continue;
+                }
+                output.set_preferred(wl_mode);
+            }
+            output.change_current_state(
+                Some(wl_mode),
+                Some(settings.transform),
+                Some(OutputScale::Fractional(settings.scale.unwrap_or(1.0))),
+                None,
+            );
+            output
+                .user_data()
+                .get::<OutputPosition>()
+                .unwrap()
+                .set(settings.position);
+            surface.compositor.reset_buffers();
+        }
+
+        crate::shell::fixup_positions(&mut self.space, self.pointer.current_location());
+        for output in self.space.outputs() {
+            let map = layer_map_for_output(output);
+            crate::shell::resize_toplevel_windows(&self.space, &map, output);
+            if let Some(window) = output
+                .user_data()
+                .get::<FullscreenSurface>()
+                .and_then(|f| f.get())
+            {
+                let geometry = self.space.output_geometry(output).unwrap();
+                window.0.toplevel().with_pending_state(|state| {
+                    state.size = Some(geometry.size);
+                });
+                window.0.toplevel().send_configure();
+            }
+        }
+
+        // outputs failing to be created are only noticed here
+        for (identity, head) in self.output_heads() {
+            let settings =
+                output_settings(&self.config, &self.backend_data.output_overrides, &identity);
+            if settings.enabled != head.enabled {
+                warn!(output = head.name, "Failed to enable output");
+                success = false;
+            }
+        }
+
+        self.update_output_heads();
+        self.map_input_devices();
+        success
+    }
+
+    /// All connected desktop outputs, enabled or not, as advertised through output management
+    fn output_heads(&self) -> Vec<(OutputIdentity, OutputHead)> {
+        let mut heads = Vec::new();
+        for (node, device) in self.backend_data.backends.iter() {
+            for (connector, crtc) in device.drm_scanner.crtcs() {
+                if device
+                    .non_desktop_connectors
+                    .iter()
+                    .any(|(handle, _)| *handle == connector.handle())
+                {
+                    continue;
+                }
+
+                let mirror = device
+                    .surfaces
+                    .get(&crtc)
+                    .and_then(|surface| surface.mirror.as_ref());
+                let output = self
+                    .space
+                    .outputs()
+                    .find(|o| {
+                        o.user_data()
+                            .get::<UdevOutputId>()
+                            .map(|id| id.device_id == *node && id.crtc == crtc)
+                            .unwrap_or(false)
+                    })
+                    .or(mirror.map(|mirror| &mirror.output));
+                let identity = match output.and_then(|o| o.user_data().get::<OutputIdentity>()) {
+                    Some(identity) => identity.clone(),
+                    None => {
+                        let (make, model) =
+                            EdidInfo::for_connector(&device.drm, connector.handle())
+                                .map(|info| (info.manufacturer, info.model))
+                                .unwrap_or_else(|| ("Unknown".into(), "Unknown".into()));
+                        OutputIdentity {
+                            connector: format!(
+                                "{}-{}",
+                                connector.interface().as_str(),
+                                connector.interface_id()
+                            ),
+                            make,
+                            model,
+                            serial: edid_serial(&device.drm, connector.handle()),
+                        }
+                    }
+                };
+
+                let modes = connector
+                    .modes()
+                    .iter()
+                    .map(|mode| {
+                        let wl_mode = WlMode::from(*mode);
+                        OutputHeadMode {
+                            size: wl_mode.size,
+                            refresh: wl_mode.refresh,
+                            preferred: mode.mode_type().contains(ModeTypeFlags::PREFERRED),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                let current_mode = output.and_then(|o| o.current_mode()).and_then(|current| {
+                    modes.iter().position(|mode| {
+                        mode.size == current.size && mode.refresh == current.refresh
+                    })
+                });
+                let (phys_w, phys_h) = connector.size().unwrap_or((0, 0));
+
+                let head = OutputHead {
+                    name: identity.connector.clone(),
+                    description: format!(
+                        "{} {} ({})",
+                        identity.make, identity.model, identity.connector
+                    ),
+                    make: identity.make.clone(),
+                    model: identity.model.clone(),
+                    serial: identity.serial.clone(),
+                    physical_size: (phys_w as i32, phys_h as i32),
+                    modes,
+                    enabled: output.is_some(),
+                    current_mode,
+                    // mirrors are reported at the position of the output they show
+                    position: mirror
+                        .and_then(|mirror| self.space.outputs().find(|o| o.name() == mirror.source))
+                        .or(output)
+                        .and_then(|o| self.space.output_geometry(o))
+                        .map(|geo| geo.loc)
+                        .unwrap_or_default(),
+                    transform: output
+                        .map(|o| o.current_transform())
+                        .unwrap_or(Transform::Normal),
+                    scale: output
+                        .map(|o| o.current_scale().fractional_scale())
+                        .unwrap_or(1.0),
+                };
+                heads.push((identity, head));
+            }
+        }
+        heads.sort_by(|(a, _), (b, _)| a.connector.cmp(&b.connector));
+        heads
+    }
+
+    /// Announce changed outputs to output management clients
+    fn update_output_heads(&mut self) {
+        let heads = self
+            .output_heads()
+            .into_iter()
+            .map(|(_, head)| head)
+            .collect();
+        self.backend_data
+            .output_management_state
+            .update::<BuedchenState<UdevData>>(&self.display_handle, heads);
+    }
+
+    fn connector_connected(
+        &mut self,
+        node: DrmNode,
+        connector: connector::Info,
+        crtc: crtc::Handle,
+    ) {
+        let device = if let Some(device) = self.backend_data.backends.get_mut(&node) {
+            device
+        } else {
+            return;
+        };
+
+        let mut renderer = self
+            .backend_data
+            .gpus
+            .single_renderer(&device.render_node)
+            .unwrap();
+        let render_formats = renderer
+            .as_mut()
+            .egl_context()
+            .dmabuf_render_formats()
+            .clone();
+
+        let output_name = format!(
+            "{}-{}",
+            connector.interface().as_str(),
+            connector.interface_id()
+        );
+        info!(?crtc, "Trying to setup connector {}", output_name,);
+
+        let non_desktop = device
+            .drm
+            .get_properties(connector.handle())
+            .ok()
+            .and_then(|props| {
+                let (info, value) = props
+                    .into_iter()
+                    .filter_map(|(handle, value)| {
+                        let info = device.drm.get_property(handle).ok()?;
+
+                        Some((info, value))
+                    })
+                    .find(|(info, _)| info.name().to_str() == Ok("non-desktop"))?;
+
+                info.value_type().convert_value(value).as_boolean()
+            })
+            .unwrap_or(false);
+
+        let (make, model) = EdidInfo::for_connector(&device.drm, connector.handle())
+            .map(|info| (info.manufacturer, info.model))
+            .unwrap_or_else(|| ("Unknown".into(), "Unknown".into()));
+
+        if non_desktop {
+            info!(
+                "Connector {} is non-desktop, setting up for leasing",
+                output_name
+            );
+            device
+                .non_desktop_connectors
+                .push((connector.handle(), crtc));
+            if let Some(lease_state) = device.leasing_global.as_mut() {
+                lease_state.add_connector::<BuedchenState<UdevData>>(
+                    connector.handle(),
+                    output_name,
+                    format!("{} {}", make, model),
+                );
+            }
+        } else {
+            let identity = OutputIdentity {
+                connector: output_name.clone(),
+                make: make.clone(),
+                model: model.clone(),
+                serial: edid_serial(&device.drm, connector.handle()),
+            };
+            let settings =
+                output_settings(&self.config, &self.backend_data.output_overrides, &identity);
+            if !settings.enabled {
+                info!(?identity, "Output is disabled by the configuration");
+                return;
+            }
+            let color =
+                color_adjustment(&self.config, &self.backend_data.color_overrides, &identity);
+
+            let mode_id = select_mode(connector.modes(), settings.mode);
+
+            let drm_mode = connector.modes()[mode_id];
+            let wl_mode = WlMode::from(drm_mode);
+
+            let surface = match device
+                .drm
+                .create_surface(crtc, drm_mode, &[connector.handle()])
+            {
+                Ok(surface) => surface,
+                Err(err) => {
+                    warn!("Failed to create drm surface: {}", err);
+                    return;
+                }
             };
 
             let (phys_w, phys_h) = connector.size().unwrap_or((0, 0));
@@ -1042,16 +1991,47 @@ impl BuedchenState<UdevData> {
                     model,
                 },
             );
-            let global = output.create_global::<BuedchenState<UdevData>>(&self.display_handle);
-
-            let x = self.space.outputs().fold(0, |acc, o| {
-                acc + self.space.output_geometry(o).unwrap().size.w
+            let mirror = settings.mirror.clone().map(|source| Mirror {
+                output: output.clone(),
+                source,
+                scaling: settings.mirror_scaling,
+                settings: settings.clone(),
             });
-            let position = (x, 0).into();
+            // a mirror only repeats the content of another output, so it is neither announced to
+            // clients nor part of the space
+            let global = mirror
+                .is_none()
+                .then(|| output.create_global::<BuedchenState<UdevData>>(&self.display_handle));
+
+            let x = self
+                .space
+                .outputs()
+                .filter_map(|o| self.space.output_geometry(o))
+                .map(|geo| geo.loc.x + geo.size.w)
+                .max()
+                .unwrap_or(0);
+            let position = settings.position.unwrap_or_else(|| (x, 0).into());
 
             output.set_preferred(wl_mode);
-            output.change_current_state(Some(wl_mode), None, None, Some(position));
-            self.space.map_output(&output, position);
+            output.change_current_state(
+                Some(wl_mode),
+                Some(settings.transform),
+                settings.scale.map(OutputScale::Fractional),
+                Some(position),
+            );
+            if mirror.is_none() {
+                self.space.map_output(&output, position);
+            }
+
+            output
+                .user_data()
+                .insert_if_missing(OutputPosition::default);
+            output
+                .user_data()
+                .get::<OutputPosition>()
+                .unwrap()
+                .set(settings.position);
+            output.user_data().insert_if_missing(|| identity);
 
             output.user_data().insert_if_missing(|| UdevOutputId {
                 crtc,
@@ -1148,18 +2128,34 @@ impl BuedchenState<UdevData> {
                 dh: self.display_handle.clone(),
                 device_id: node,
                 render_node: device.render_node,
-                global: Some(global),
+                global,
                 compositor,
                 #[cfg(feature = "debug")]
                 fps: fps_ticker::Fps::default(),
                 #[cfg(feature = "debug")]
                 fps_element,
                 dmabuf_feedback,
+                mirror,
+                powered: true,
+                screencopies: Vec::new(),
+                screencopy_damage: None,
+                dim: self.backend_data.dim,
+                dim_buffer: SolidColorBuffer::default(),
+                color,
+                client_gamma: None,
             };
+            if surface.has_custom_gamma() {
+                if let Err(err) = set_gamma_ramps(&device.drm, crtc, &surface) {
+                    warn!("Failed to set gamma ramps: {}", err);
+                }
+            }
 
             device.surfaces.insert(crtc, surface);
 
             self.schedule_initial_render(node, crtc, self.handle.clone());
+            self.emit_ipc_event(Event::OutputAdded {
+                name: output.name(),
+            });
         }
     }
 
@@ -1185,7 +2181,15 @@ impl BuedchenState<UdevData> {
                 leasing_state.withdraw_connector(connector.handle());
             }
         } else {
-            device.surfaces.remove(&crtc);
+            if device.surfaces.remove(&crtc).is_some() {
+                self.emit_ipc_event(Event::OutputRemoved {
+                    name: format!(
+                        "{}-{}",
+                        connector.interface().as_str(),
+                        connector.interface_id()
+                    ),
+                });
+            }
 
             let output = self
                 .space
@@ -1199,6 +2203,10 @@ impl BuedchenState<UdevData> {
                 .cloned();
 
             if let Some(output) = output {
+                self.backend_data.output_power_state.output_removed(&output);
+                self.backend_data
+                    .gamma_control_state
+                    .output_removed(&output);
                 self.space.unmap_output(&output);
             }
         }
@@ -1231,6 +2239,8 @@ impl BuedchenState<UdevData> {
 
         // fixup window coordinates
         crate::shell::fixup_positions(&mut self.space, self.pointer.current_location());
+        self.update_output_heads();
+        self.map_input_devices();
     }
 
     fn device_removed(&mut self, node: DrmNode) {
@@ -1269,6 +2279,8 @@ impl BuedchenState<UdevData> {
         }
 
         crate::shell::fixup_positions(&mut self.space, self.pointer.current_location());
+        self.update_output_heads();
+        self.map_input_devices();
     }
 
     fn frame_finish(
@@ -1295,14 +2307,20 @@ impl BuedchenState<UdevData> {
             }
         };
 
-        let output = if let Some(output) = self.space.outputs().find(|o| {
-            o.user_data().get::<UdevOutputId>()
-                == Some(&UdevOutputId {
-                    device_id: surface.device_id,
-                    crtc,
-                })
-        }) {
-            output.clone()
+        let output = if let Some(output) = self
+            .space
+            .outputs()
+            .find(|o| {
+                o.user_data().get::<UdevOutputId>()
+                    == Some(&UdevOutputId {
+                        device_id: surface.device_id,
+                        crtc,
+                    })
+            })
+            .cloned()
+            .or_else(|| surface.mirror.as_ref().map(|mirror| mirror.output.clone()))
+        {
+            output
         } else {
             // somehow we got called with an invalid output
             return;
@@ -1468,6 +2486,12 @@ impl BuedchenState<UdevData> {
             return;
         };
 
+        // no frames are scheduled while the output is off, turning it on starts rendering again
+        if !surface.powered || self.backend_data.blanked {
+            surface.screencopies.clear();
+            return;
+        }
+
         let start = Instant::now();
 
         // TODO get scale from the rendersurface when supporting HiDPI
@@ -1523,32 +2547,62 @@ impl BuedchenState<UdevData> {
                 texture
             });
 
-        let output = if let Some(output) = self.space.outputs().find(|o| {
-            o.user_data().get::<UdevOutputId>()
-                == Some(&UdevOutputId {
-                    device_id: surface.device_id,
-                    crtc,
-                })
-        }) {
-            output.clone()
+        let mirror = surface
+            .mirror
+            .as_ref()
+            .map(|mirror| (mirror.output.clone(), mirror.source.clone(), mirror.scaling));
+        let output = if let Some(output) = self
+            .space
+            .outputs()
+            .find(|o| {
+                o.user_data().get::<UdevOutputId>()
+                    == Some(&UdevOutputId {
+                        device_id: surface.device_id,
+                        crtc,
+                    })
+            })
+            .cloned()
+            .or_else(|| mirror.as_ref().map(|(output, _, _)| output.clone()))
+        {
+            output
         } else {
             // somehow we got called with an invalid output
             return;
         };
 
-        let result = render_surface(
-            surface,
-            &mut renderer,
-            &self.space,
-            &output,
-            self.pointer.current_location(),
-            &pointer_image,
-            &mut self.backend_data.pointer_element,
-            &self.dnd_icon,
-            &mut self.cursor_status.lock().unwrap(),
-            &self.clock,
-            self.show_window_preview,
-        );
+        let result = if let Some((_, source, scaling)) = mirror {
+            let source = self.space.outputs().find(|o| o.name() == source).cloned();
+            render_mirror(
+                surface,
+                &mut renderer,
+                &self.space,
+                &output,
+                source.as_ref(),
+                scaling,
+                self.pointer.current_location(),
+                &pointer_image,
+                &mut self.backend_data.pointer_element,
+                &self.dnd_icon,
+                &mut self.cursor_status.lock().unwrap(),
+                self.show_window_preview,
+                &self.config.colors,
+            )
+        } else {
+            render_surface(
+                surface,
+                &mut renderer,
+                &self.space,
+                &output,
+                self.pointer.current_location(),
+                &pointer_image,
+                &mut self.backend_data.pointer_element,
+                &self.dnd_icon,
+                &mut self.cursor_status.lock().unwrap(),
+                &self.clock,
+                self.show_window_preview,
+                &self.config.colors,
+            )
+        };
         let reschedule = match &result {
             Ok(has_rendered) => !has_rendered,
             Err(err) => {
@@ -1619,7 +2673,7 @@ impl BuedchenState<UdevData> {
         let node = surface.render_node;
         let result = {
             let mut renderer = self.backend_data.gpus.single_renderer(&node).unwrap();
-            initial_render(surface, &mut renderer)
+            initial_render(surface, &mut renderer, self.config.colors.background.0)
         };
 
         if let Err(err) = result {
@@ -1653,7 +2707,245 @@ fn render_surface<'a, 'b>(
     cursor_status: &mut CursorImageStatus,
     clock: &Clock<Monotonic>,
     show_window_preview: bool,
+    colors: &ColorConfig,
+) -> Result<bool, SwapBuffersError> {
+    let (elements, clear_color) = surface_elements(
+        surface,
+        renderer,
+        space,
+        output,
+        pointer_location,
+        pointer_image,
+        pointer_element,
+        dnd_icon,
+        cursor_status,
+        show_window_preview,
+        colors,
+    );
+    let res =
+        surface
+            .compositor
+            .render_frame::<_, _, GlesTexture>(renderer, &elements, clear_color)?;
+
+    post_repaint(
+        output,
+        &res.states,
+        space,
+        surface
+            .dmabuf_feedback
+            .as_ref()
+            .map(|feedback| SurfaceDmabufFeedback {
+                render_feedback: &feedback.render_feedback,
+                scanout_feedback: &feedback.scanout_feedback,
+            }),
+        clock.now(),
+    );
+
+    if res.rendered {
+        let output_presentation_feedback = take_presentation_feedback(output, space, &res.states);
+        surface
+            .compositor
+            .queue_frame(res.sync, res.damage, Some(output_presentation_feedback))
+            .map_err(Into::<SwapBuffersError>::into)?;
+    }
+
+    render_screencopies(surface, renderer, output, &elements, clear_color, clock);
+
+    Ok(res.rendered)
+}
+
+/// Copy the output into the buffers of waiting screencopy frames
+///
+/// Damage-tracked frames stay queued until the content of the output changed.
+fn render_screencopies<'a, 'b>(
+    surface: &mut SurfaceData,
+    renderer: &mut UdevRenderer<'a, 'b>,
+    output: &Output,
+    elements: &[OutputRenderElements<
+        UdevRenderer<'a, 'b>,
+        WindowRenderElement<UdevRenderer<'a, 'b>>,
+    >],
+    clear_color: [f32; 4],
+    clock: &Clock<Monotonic>,
+) {
+    if surface.screencopies.is_empty() {
+        return;
+    }
+
+    let damage = if surface.screencopies.iter().any(Screencopy::with_damage) {
+        let damage_tracker = surface
+            .screencopy_damage
+            .get_or_insert_with(|| OutputDamageTracker::from_output(output));
+        match damage_tracker.damage_output(1, elements) {
+            Ok((damage, _)) => damage.unwrap_or_default(),
+            Err(err) => {
+                warn!(?err, "Failed to compute screencopy damage");
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    for screencopy in std::mem::take(&mut surface.screencopies) {
+        if screencopy.with_damage() && damage.is_empty() {
+            surface.screencopies.push(screencopy);
+            continue;
+        }
+
+        let elements = if screencopy.overlay_cursor() {
+            elements
+        } else {
+            without_cursor(elements)
+        };
+        match copy_output(renderer, output, &screencopy, elements, clear_color) {
+            Ok(y_invert) => screencopy.submit(y_invert, &damage, clock.now().into()),
+            Err(err) => warn!("Failed to copy output: {:?}", err),
+        }
+    }
+}
+
+/// Render the output into the buffer of a screencopy frame, returns whether it is upside down
+fn copy_output<'a, 'b, E>(
+    renderer: &mut UdevRenderer<'a, 'b>,
+    output: &Output,
+    screencopy: &Screencopy,
+    elements: &[E],
+    clear_color: [f32; 4],
+) -> Result<bool, SwapBuffersError>
+where
+    E: RenderElement<UdevRenderer<'a, 'b>>,
+{
+    let render = |renderer: &mut UdevRenderer<'a, 'b>| -> Result<(), SwapBuffersError> {
+        OutputDamageTracker::from_output(output)
+            .render_output(renderer, 0, elements, clear_color)
+            .map(|_| ())
+            .map_err(|err| match err {
+                OutputDamageTrackerError::Rendering(err) => err.into(),
+                OutputDamageTrackerError::OutputNoMode(err) => {
+                    SwapBuffersError::TemporaryFailure(Box::new(err))
+                }
+            })
+    };
+
+    if let Ok(dmabuf) = get_dmabuf(screencopy.buffer()) {
+        renderer
+            .bind(dmabuf)
+            .map_err(Into::<SwapBuffersError>::into)?;
+        render(renderer)?;
+        return Ok(false);
+    }
+
+    // shm buffers are filled from an offscreen copy of the whole output
+    let region = screencopy.region();
+    let size = output
+        .current_mode()
+        .map(|mode| (mode.size.w, mode.size.h).into())
+        .unwrap_or_default();
+    let texture: GlesTexture = renderer
+        .create_buffer(Fourcc::Abgr8888, size)
+        .map_err(Into::<SwapBuffersError>::into)?;
+    renderer
+        .bind(texture)
+        .map_err(Into::<SwapBuffersError>::into)?;
+    render(renderer)?;
+    let mapping = renderer
+        .copy_framebuffer(region, Fourcc::Argb8888)
+        .map_err(Into::<SwapBuffersError>::into)?;
+    let pixels = renderer
+        .map_texture(&mapping)
+        .map_err(Into::<SwapBuffersError>::into)?;
+
+    let row_length = region.size.w as usize * 4;
+    with_buffer_contents_mut(screencopy.buffer(), |ptr, len, data| {
+        // Safety: the buffer was checked to be large enough when the copy was requested
+        let buffer = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+        for (row, line) in pixels.chunks_exact(row_length).enumerate() {
+            let start = data.offset as usize + row * data.stride as usize;
+            buffer[start..start + row_length].copy_from_slice(line);
+        }
+    })
+    .map_err(|err| SwapBuffersError::TemporaryFailure(Box::new(err)))?;
+
+    Ok(mapping.flipped())
+}
+
+/// Render the content of `source` onto the mirror `output`
+///
+/// Frame callbacks and presentation feedback are left to the source output.
+#[allow(clippy::too_many_arguments)]
+#[profiling::function]
+fn render_mirror<'a, 'b>(
+    surface: &'a mut SurfaceData,
+    renderer: &mut UdevRenderer<'a, 'b>,
+    space: &Space<WindowElement>,
+    output: &Output,
+    source: Option<&Output>,
+    scaling: MirrorScaling,
+    pointer_location: Point<f64, Logical>,
+    pointer_image: &TextureBuffer<MultiTexture>,
+    pointer_element: &mut PointerElement<MultiTexture>,
+    dnd_icon: &Option<wl_surface::WlSurface>,
+    cursor_status: &mut CursorImageStatus,
+    show_window_preview: bool,
+    colors: &ColorConfig,
 ) -> Result<bool, SwapBuffersError> {
+    let (elements, clear_color) = match source {
+        Some(source) => {
+            let (elements, clear_color) = surface_elements(
+                surface,
+                renderer,
+                space,
+                source,
+                pointer_location,
+                pointer_image,
+                pointer_element,
+                dnd_icon,
+                cursor_status,
+                show_window_preview,
+                colors,
+            );
+            (
+                mirror_elements(elements, source, output, scaling),
+                clear_color,
+            )
+        }
+        // the mirrored output is disabled or not connected
+        None => (Vec::new(), colors.background.0),
+    };
+    let res =
+        surface
+            .compositor
+            .render_frame::<_, _, GlesTexture>(renderer, &elements, clear_color)?;
+
+    if res.rendered {
+        surface
+            .compositor
+            .queue_frame(res.sync, res.damage, None)
+            .map_err(Into::<SwapBuffersError>::into)?;
+    }
+
+    Ok(res.rendered)
+}
+
+/// Elements and clear colour of an output, including cursor, drag-and-drop icon and dimming
+#[allow(clippy::too_many_arguments)]
+fn surface_elements<'a, 'b>(
+    surface: &mut SurfaceData,
+    renderer: &mut UdevRenderer<'a, 'b>,
+    space: &Space<WindowElement>,
+    output: &Output,
+    pointer_location: Point<f64, Logical>,
+    pointer_image: &TextureBuffer<MultiTexture>,
+    pointer_element: &mut PointerElement<MultiTexture>,
+    dnd_icon: &Option<wl_surface::WlSurface>,
+    cursor_status: &mut CursorImageStatus,
+    show_window_preview: bool,
+    colors: &ColorConfig,
+) -> (
+    Vec<OutputRenderElements<UdevRenderer<'a, 'b>, WindowRenderElement<UdevRenderer<'a, 'b>>>>,
+    [f32; 4],
+) {
     let output_geometry = space.output_geometry(output).unwrap();
     let scale = Scale::from(output.current_scale().fractional_scale());
 
@@ -1718,6 +3010,28 @@ fn render_surface<'a, 'b>(
         }
     }
 
+    if surface.dim > 0.0 {
+        let size = output
+            .current_mode()
+            .map(|mode| output.current_transform().transform_size(mode.size))
+            .unwrap_or_default()
+            .to_f64()
+            .to_logical(scale)
+            .to_i32_ceil();
+        surface
+            .dim_buffer
+            .update(size, [0.0, 0.0, 0.0, surface.dim]);
+        custom_elements.push(CustomRenderElements::Dim(
+            SolidColorRenderElement::from_buffer(
+                &surface.dim_buffer,
+                (0, 0),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            ),
+        ));
+    }
+
     #[cfg(feature = "debug")]
     if let Some(element) = surface.fps_element.as_mut() {
         element.update_fps(surface.fps.avg().round() as u32);
@@ -1725,52 +3039,360 @@ fn render_surface<'a, 'b>(
         custom_elements.push(CustomRenderElements::Fps(element.clone()));
     }
 
-    let (elements, clear_color) = output_elements(
+    output_elements(
         output,
         space,
         custom_elements,
         renderer,
         show_window_preview,
-    );
-    let res =
-        surface
-            .compositor
-            .render_frame::<_, _, GlesTexture>(renderer, &elements, clear_color)?;
-
-    post_repaint(
-        output,
-        &res.states,
-        space,
-        surface
-            .dmabuf_feedback
-            .as_ref()
-            .map(|feedback| SurfaceDmabufFeedback {
-                render_feedback: &feedback.render_feedback,
-                scanout_feedback: &feedback.scanout_feedback,
-            }),
-        clock.now(),
-    );
-
-    if res.rendered {
-        let output_presentation_feedback = take_presentation_feedback(output, space, &res.states);
-        surface
-            .compositor
-            .queue_frame(res.sync, res.damage, Some(output_presentation_feedback))
-            .map_err(Into::<SwapBuffersError>::into)?;
-    }
-
-    Ok(res.rendered)
+        colors,
+    )
 }
 
 fn initial_render(
     surface: &mut SurfaceData,
     renderer: &mut UdevRenderer<'_, '_>,
+    clear_color: [f32; 4],
 ) -> Result<(), SwapBuffersError> {
     surface
         .compositor
-        .render_frame::<_, CustomRenderElements<_>, GlesTexture>(renderer, &[], CLEAR_COLOR)?;
+        .render_frame::<_, CustomRenderElements<_>, GlesTexture>(renderer, &[], clear_color)?;
     surface.compositor.queue_frame(None, None, None)?;
     surface.compositor.reset_buffers();
 
     Ok(())
 }
+
+/// Colour adjustment from the configuration, unless it has been changed at runtime
+fn color_adjustment(
+    config: &Config,
+    overrides: &HashMap<String, ColorAdjustment>,
+    identity: &OutputIdentity,
+) -> ColorAdjustment {
+    overrides
+        .get(&identity.connector)
+        .copied()
+        .unwrap_or_else(|| config.color_adjustment(identity))
+}
+
+/// Program the gamma ramps of a surface, from a gamma control client or its colour adjustment
+fn set_gamma_ramps(
+    drm: &DrmDevice,
+    crtc: crtc::Handle,
+    surface: &SurfaceData,
+) -> std::io::Result<()> {
+    let size = drm.get_crtc(crtc)?.gamma_length() as usize;
+    if size == 0 {
+        return Ok(());
+    }
+    let ramps = match &surface.client_gamma {
+        Some(ramps) if ramps.len() == size * 3 => ramps.clone(),
+        _ => color_ramps(&surface.color, size),
+    };
+    let (red, rest) = ramps.split_at(size);
+    let (green, blue) = rest.split_at(size);
+    drm.set_gamma(crtc, red, green, blue)
+}
+
+/// Relative difference up to which the size of a touchscreen matches the size of a panel
+const SIZE_TOLERANCE: f64 = 0.05;
+
+/// The only item of an iterator, `None` if it is empty or has several items
+fn single<T>(mut iter: impl Iterator<Item = T>) -> Option<T> {
+    let item = iter.next()?;
+    iter.next().is_none().then_some(item)
+}
+
+/// Input rules matching a libinput device, in the order of the configuration
+fn matching_input_rules<'a>(
+    rules: &'a [InputRule],
+    device: &libinput::Device,
+) -> Vec<&'a InputRule> {
+    let id = DeviceId {
+        vendor: device.id_vendor(),
+        product: device.id_product(),
+    };
+    let touchpad = device.config_tap_finger_count() > 0;
+    let has_type = |device_type| match device_type {
+        DeviceType::Keyboard => device.has_capability(libinput::DeviceCapability::Keyboard),
+        DeviceType::Pointer => {
+            device.has_capability(libinput::DeviceCapability::Pointer) && !touchpad
+        }
+        DeviceType::Touchpad => touchpad,
+        DeviceType::Touch => device.has_capability(libinput::DeviceCapability::Touch),
+        DeviceType::Tablet => device.has_capability(libinput::DeviceCapability::TabletTool),
+    };
+    rules
+        .iter()
+        .filter(|rule| rule.matches(device.name(), id, &has_type))
+        .collect()
+}
+
+/// Apply the libinput settings of all rules matching `device`
+///
+/// Settings no rule sets go back to the device defaults, so removing them from the
+/// configuration takes effect on reload.
+fn configure_input_device(rules: &[InputRule], device: &mut libinput::Device) {
+    let rules = matching_input_rules(rules, device);
+    debug!(
+        device = device.name(),
+        rules = rules.len(),
+        "Configuring input device"
+    );
+    // the last matching rule setting a value wins
+    let rules = rules.iter().rev();
+
+    let tap = rules.clone().find_map(|rule| rule.tap);
+    apply_input_setting(
+        device,
+        "tap",
+        tap,
+        |device| {
+            (device.config_tap_finger_count() > 0).then(|| device.config_tap_default_enabled())
+        },
+        |device, tap| device.config_tap_set_enabled(tap),
+    );
+    let drag = rules.clone().find_map(|rule| rule.drag);
+    apply_input_setting(
+        device,
+        "drag",
+        drag,
+        |device| {
+            (device.config_tap_finger_count() > 0).then(|| device.config_tap_default_drag_enabled())
+        },
+        |device, drag| device.config_tap_set_drag_enabled(drag),
+    );
+
+    let profile = rules
+        .clone()
+        .find_map(|rule| rule.accel_profile)
+        .map(|profile| match profile {
+            AccelProfile::Flat => libinput::AccelProfile::Flat,
+            AccelProfile::Adaptive => libinput::AccelProfile::Adaptive,
+        });
+    apply_input_setting(
+        device,
+        "accel_profile",
+        profile,
+        |device| device.config_accel_default_profile(),
+        |device, profile| device.config_accel_set_profile(profile),
+    );
+    let speed = rules.clone().find_map(|rule| rule.accel_speed);
+    apply_input_setting(
+        device,
+        "accel_speed",
+        speed,
+        |device| {
+            device
+                .config_accel_is_available()
+                .then(|| device.config_accel_default_speed())
+        },
+        |device, speed| device.config_accel_set_speed(speed),
+    );
+
+    let natural = rules.clone().find_map(|rule| rule.natural_scroll);
+    apply_input_setting(
+        device,
+        "natural_scroll",
+        natural,
+        |device| {
+            device
+                .config_scroll_has_natural_scroll()
+                .then(|| device.config_scroll_default_natural_scroll_enabled())
+        },
+        |device, natural| device.config_scroll_set_natural_scroll_enabled(natural),
+    );
+    let method = rules
+        .clone()
+        .find_map(|rule| rule.scroll_method)
+        .map(|method| match method {
+            ScrollMethod::None => libinput::ScrollMethod::NoScroll,
+            ScrollMethod::TwoFinger => libinput::ScrollMethod::TwoFinger,
+            ScrollMethod::Edge => libinput::ScrollMethod::Edge,
+            ScrollMethod::OnButtonDown => libinput::ScrollMethod::OnButtonDown,
+        });
+    apply_input_setting(
+        device,
+        "scroll_method",
+        method,
+        |device| device.config_scroll_default_method(),
+        |device, method| device.config_scroll_set_method(method),
+    );
+
+    let left = rules.clone().find_map(|rule| rule.left_handed);
+    apply_input_setting(
+        device,
+        "left_handed",
+        left,
+        |device| {
+            device
+                .config_left_handed_is_available()
+                .then(|| device.config_left_handed_default())
+        },
+        |device, left| device.config_left_handed_set(left),
+    );
+    let dwt = rules.clone().find_map(|rule| rule.dwt);
+    apply_input_setting(
+        device,
+        "dwt",
+        dwt,
+        |device| {
+            device
+                .config_dwt_is_available()
+                .then(|| device.config_dwt_default_enabled())
+        },
+        |device, dwt| device.config_dwt_set_enabled(dwt),
+    );
+
+    let mode = rules
+        .clone()
+        .find_map(|rule| rule.send_events)
+        .map(|mode| match mode {
+            SendEvents::Enabled => libinput::SendEventsMode::ENABLED,
+            SendEvents::Disabled => libinput::SendEventsMode::DISABLED,
+            SendEvents::DisabledOnExternalMouse => {
+                libinput::SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE
+            }
+        });
+    // every device can be enabled, libinput rejects the other modes if they are not supported
+    apply_input_setting(
+        device,
+        "send_events",
+        mode,
+        |_| Some(libinput::SendEventsMode::ENABLED),
+        |device, mode| device.config_send_events_set_mode(mode),
+    );
+
+    let matrix = rules.clone().find_map(|rule| rule.calibration_matrix);
+    apply_input_setting(
+        device,
+        "calibration_matrix",
+        matrix,
+        |device| {
+            device
+                .config_calibration_default_matrix()
+                .filter(|_| device.config_calibration_has_matrix())
+        },
+        |device, matrix| device.config_calibration_set_matrix(matrix),
+    );
+}
+
+/// Set a libinput setting to `value`, or back to the default, which is `None` if the device
+/// doesn't support the setting
+fn apply_input_setting<T: Copy + std::fmt::Debug>(
+    device: &mut libinput::Device,
+    setting: &str,
+    value: Option<T>,
+    default: impl FnOnce(&libinput::Device) -> Option<T>,
+    set: impl FnOnce(&mut libinput::Device, T) -> DeviceConfigResult,
+) {
+    let Some(default) = default(device) else {
+        if value.is_some() {
+            warn!(
+                device = device.name(),
+                setting, "Input setting not supported by the device"
+            );
+        }
+        return;
+    };
+    let value = value.unwrap_or(default);
+    if let Err(err) = set(device, value) {
+        warn!(
+            device = device.name(),
+            setting,
+            ?value,
+            ?err,
+            "Failed to apply input setting"
+        );
+    }
+}
+
+/// Output settings from the configuration, unless they have been changed at runtime
+fn output_settings(
+    config: &Config,
+    overrides: &HashMap<String, OutputSettings>,
+    identity: &OutputIdentity,
+) -> OutputSettings {
+    overrides
+        .get(&identity.connector)
+        .cloned()
+        .unwrap_or_else(|| config.output_settings(identity))
+}
+
+/// Index of the mode to use for a newly connected output
+fn select_mode(modes: &[DrmMode], selection: ModeSelection) -> usize {
+    let preferred = modes
+        .iter()
+        .position(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+        .unwrap_or(0);
+    let pixels = |mode: &DrmMode| {
+        let (w, h) = mode.size();
+        w as u32 * h as u32
+    };
+    let refresh = |mode: &DrmMode| WlMode::from(*mode).refresh;
+
+    let selected = match selection {
+        ModeSelection::Preferred => None,
+        ModeSelection::HighestResolution => modes
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, mode)| (pixels(mode), refresh(mode)))
+            .map(|(index, _)| index),
+        ModeSelection::HighestRefresh => modes
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, mode)| (refresh(mode), pixels(mode)))
+            .map(|(index, _)| index),
+        ModeSelection::Exact {
+            width,
+            height,
+            refresh: wanted,
+        } => {
+            let index = modes
+                .iter()
+                .enumerate()
+                .filter(|(_, mode)| mode.size() == (width, height))
+                .min_by_key(|(_, mode)| match wanted {
+                    Some(wanted) => (refresh(mode) - wanted as i32).abs(),
+                    None => -refresh(mode),
+                })
+                .map(|(index, _)| index);
+            if index.is_none() {
+                warn!(
+                    ?selection,
+                    "Configured mode is not supported, using the preferred mode"
+                );
+            }
+            index
+        }
+    };
+    selected.unwrap_or(preferred)
+}
+
+/// Serial number of the display connected to `connector`, read from its EDID
+fn edid_serial(drm: &DrmDevice, connector: connector::Handle) -> Option<String> {
+    let props = drm.get_properties(connector).ok()?;
+    let (_, value) = props.into_iter().find(|(handle, _)| {
+        drm.get_property(*handle)
+            .map(|info| info.name().to_str() == Ok("EDID"))
+            .unwrap_or(false)
+    })?;
+    let edid = drm.get_property_blob(value).ok()?;
+    if edid.len() < 128 {
+        return None;
+    }
+
+    // prefer the serial number string descriptor over the numeric serial
+    let descriptor = (54..126).step_by(18).find_map(|offset| {
+        let descriptor = &edid[offset..offset + 18];
+        (descriptor[..5] == [0, 0, 0, 0xff, 0]).then(|| {
+            String::from_utf8_lossy(&descriptor[5..])
+                .trim_end_matches(|c: char| c == '\n' || c.is_whitespace())
+                .to_string()
+        })
+    });
+    descriptor.filter(|serial| !serial.is_empty()).or_else(|| {
+        let serial = u32::from_le_bytes([edid[12], edid[13], edid[14], edid[15]]);
+        (serial != 0).then(|| serial.to_string())
+    })
+}
@@ -0,0 +1,342 @@
+use std::{sync::atomic::Ordering, sync::Mutex, time::Duration};
+
+use smithay::{
+    backend::{
+        allocator::dmabuf::Dmabuf,
+        renderer::{
+            damage::{Error as OutputDamageTrackerError, OutputDamageTracker},
+            element::AsRenderElements,
+            gles::{GlesRenderer, GlesTexture},
+            ImportDma, ImportMemWl,
+        },
+        winit::{self, WinitEvent, WinitGraphicsBackend},
+        SwapBuffersError,
+    },
+    delegate_dmabuf,
+    desktop::{layer_map_for_output, Space},
+    input::pointer::{CursorImageAttributes, CursorImageStatus},
+    output::{Mode, Output, PhysicalProperties, Subpixel},
+    reexports::{
+        calloop::EventLoop,
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
+        wayland_server::{protocol::wl_surface, Display},
+        winit::platform::pump_events::PumpStatus,
+    },
+    utils::{IsAlive, Scale, Transform},
+    wayland::{
+        compositor,
+        dmabuf::{DmabufGlobal, DmabufHandler, DmabufState, ImportError},
+    },
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    client::ClientSupervisor,
+    config::{reload_on_sighup, ColorConfig, Config, ReloadReport},
+    drawing::PointerElement,
+    render::{render_output, CustomRenderElements},
+    screenshot::{render_screenshot, screenshot_on_sigusr1, Screenshot},
+    shell::{fixup_positions, resize_toplevel_windows, WindowElement},
+    state::{post_repaint, take_presentation_feedback, Backend, BuedchenState, CalloopData},
+};
+
+pub const OUTPUT_NAME: &str = "winit";
+
+pub struct WinitData {
+    backend: WinitGraphicsBackend<GlesRenderer>,
+    damage_tracker: OutputDamageTracker,
+    dmabuf_state: (DmabufState, DmabufGlobal),
+    pointer_element: PointerElement<GlesTexture>,
+    full_redraw: u8,
+}
+
+impl std::fmt::Debug for WinitData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WinitData")
+            .field("full_redraw", &self.full_redraw)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DmabufHandler for BuedchenState<WinitData> {
+    fn dmabuf_state(&mut self) -> &mut DmabufState {
+        &mut self.backend_data.dmabuf_state.0
+    }
+
+    fn dmabuf_imported(
+        &mut self,
+        _global: &DmabufGlobal,
+        dmabuf: Dmabuf,
+    ) -> Result<(), ImportError> {
+        self.backend_data
+            .backend
+            .renderer()
+            .import_dmabuf(&dmabuf, None)
+            .map(|_| ())
+            .map_err(|_| ImportError::Failed)
+    }
+}
+delegate_dmabuf!(BuedchenState<WinitData>);
+
+impl Backend for WinitData {
+    fn seat_name(&self) -> String {
+        String::from("winit")
+    }
+
+    fn reset_buffers(&mut self, _output: &Output) {
+        self.full_redraw = 4;
+    }
+
+    fn early_import(&mut self, _surface: &wl_surface::WlSurface) {}
+
+    fn render_screenshot(
+        &mut self,
+        output: &Output,
+        space: &Space<WindowElement>,
+        show_window_preview: bool,
+        colors: &ColorConfig,
+    ) -> Result<Screenshot, Box<dyn std::error::Error>> {
+        render_screenshot::<_, GlesTexture>(
+            self.backend.renderer(),
+            output,
+            space,
+            show_window_preview,
+            colors,
+        )
+    }
+}
+
+pub fn run_winit(client: ClientSupervisor, config: Config) {
+    let mut event_loop = EventLoop::try_new().unwrap();
+    let display = Display::new().unwrap();
+    let mut display_handle = display.handle();
+
+    let (mut backend, mut winit) = match winit::init::<GlesRenderer>() {
+        Ok(ret) => ret,
+        Err(err) => {
+            error!("Failed to initialize Winit backend: {}", err);
+            return;
+        }
+    };
+    let size = backend.window_size();
+
+    let mode = Mode {
+        size,
+        refresh: 60_000,
+    };
+    let output = Output::new(
+        OUTPUT_NAME.to_string(),
+        PhysicalProperties {
+            size: (0, 0).into(),
+            subpixel: Subpixel::Unknown,
+            make: "buedchen".into(),
+            model: "Winit".into(),
+        },
+    );
+    output.create_global::<BuedchenState<WinitData>>(&display_handle);
+    output.change_current_state(
+        Some(mode),
+        Some(Transform::Flipped180),
+        None,
+        Some((0, 0).into()),
+    );
+    output.set_preferred(mode);
+
+    let dmabuf_formats = backend.renderer().dmabuf_formats().collect::<Vec<_>>();
+    let mut dmabuf_state = DmabufState::new();
+    let dmabuf_global =
+        dmabuf_state.create_global::<BuedchenState<WinitData>>(&display_handle, dmabuf_formats);
+    let shm_formats = backend.renderer().shm_formats();
+
+    let data = WinitData {
+        damage_tracker: OutputDamageTracker::from_output(&output),
+        backend,
+        dmabuf_state: (dmabuf_state, dmabuf_global),
+        pointer_element: PointerElement::default(),
+        full_redraw: 0,
+    };
+    let mut state = BuedchenState::init(display, event_loop.handle(), data, true, client, config);
+    state.shm_state.update_formats(shm_formats);
+    state.space.map_output(&output, (0, 0));
+
+    if let Err(err) = reload_on_sighup(&mut state, BuedchenState::reload_winit_config) {
+        warn!("Unable to reload the configuration on SIGHUP: {}", err);
+    }
+    if let Err(err) = screenshot_on_sigusr1(&event_loop.handle()) {
+        warn!("Unable to take screenshots on SIGUSR1: {}", err);
+    }
+
+    if let Err(e) = state.start_client() {
+        error!("Couldn't start client: {}", e);
+        return;
+    }
+    info!("Client started successfully");
+
+    while state.running.load(Ordering::SeqCst) {
+        let status = winit.dispatch_new_events(|event| match event {
+            WinitEvent::Resized { size, .. } => {
+                // We only have one output
+                let mode = Mode {
+                    size,
+                    refresh: 60_000,
+                };
+                output.change_current_state(Some(mode), None, None, None);
+                output.set_preferred(mode);
+                state.space.map_output(&output, (0, 0));
+                fixup_positions(&mut state.space, state.pointer.current_location());
+
+                let mut map = layer_map_for_output(&output);
+                map.arrange();
+                resize_toplevel_windows(&state.space, &map, &output);
+                state.backend_data.full_redraw = 4;
+            }
+            WinitEvent::Input(event) => {
+                state.process_input_event_windowed(&display_handle, event, OUTPUT_NAME)
+            }
+            _ => (),
+        });
+
+        if let PumpStatus::Exit(_) = status {
+            state.running.store(false, Ordering::SeqCst);
+            break;
+        }
+
+        state.render_winit(&output);
+
+        let mut calloop_data = CalloopData {
+            state,
+            display_handle,
+        };
+        let result = event_loop.dispatch(Some(Duration::from_millis(1)), &mut calloop_data);
+        CalloopData {
+            state,
+            display_handle,
+        } = calloop_data;
+
+        if result.is_err() {
+            state.running.store(false, Ordering::SeqCst);
+        } else {
+            state.space.refresh();
+            state.popups.cleanup();
+            display_handle.flush_clients().unwrap();
+        }
+
+        state.check_client();
+    }
+}
+
+impl BuedchenState<WinitData> {
+    fn reload_winit_config(&mut self) -> Result<ReloadReport, String> {
+        let (_, mut report) = self.reload_config()?;
+        // the output follows the window, the host draws the cursor and owns the input devices
+        report.not_applied(&["output", "cursor", "idle", "schedule", "inputs"]);
+        Ok(report)
+    }
+
+    fn render_winit(&mut self, output: &Output) {
+        let backend_data = &mut self.backend_data;
+        backend_data.full_redraw = backend_data.full_redraw.saturating_sub(1);
+
+        let mut cursor_guard = self.cursor_status.lock().unwrap();
+
+        // reset the cursor if the surface is no longer alive
+        let mut reset = false;
+        if let CursorImageStatus::Surface(ref surface) = *cursor_guard {
+            reset = !surface.alive();
+        }
+        if reset {
+            *cursor_guard = CursorImageStatus::default_named();
+        }
+        // named cursors are drawn by the host, client cursors by us
+        let cursor_visible = matches!(*cursor_guard, CursorImageStatus::Named(_));
+        backend_data
+            .pointer_element
+            .set_status(cursor_guard.clone());
+
+        let scale = Scale::from(output.current_scale().fractional_scale());
+        let cursor_hotspot = if let CursorImageStatus::Surface(ref surface) = *cursor_guard {
+            compositor::with_states(surface, |states| {
+                states
+                    .data_map
+                    .get::<Mutex<CursorImageAttributes>>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .hotspot
+            })
+        } else {
+            (0, 0).into()
+        };
+        drop(cursor_guard);
+        let cursor_pos = self.pointer.current_location() - cursor_hotspot.to_f64();
+        let cursor_pos_scaled = cursor_pos.to_physical(scale).to_i32_round();
+
+        let age = if backend_data.full_redraw > 0 {
+            0
+        } else {
+            backend_data.backend.buffer_age().unwrap_or(0)
+        };
+
+        let space = &self.space;
+        let show_window_preview = self.show_window_preview;
+        let colors = &self.config.colors;
+        let damage_tracker = &mut backend_data.damage_tracker;
+        let pointer_element = &backend_data.pointer_element;
+        let backend = &mut backend_data.backend;
+        let render_res = backend.bind().and_then(|_| {
+            let renderer = backend.renderer();
+            let elements: Vec<CustomRenderElements<GlesRenderer>> =
+                pointer_element.render_elements(renderer, cursor_pos_scaled, scale, 1.0);
+
+            render_output(
+                output,
+                space,
+                elements,
+                renderer,
+                damage_tracker,
+                age,
+                show_window_preview,
+                colors,
+            )
+            .map_err(|err| match err {
+                OutputDamageTrackerError::Rendering(err) => err.into(),
+                _ => unreachable!(),
+            })
+        });
+
+        match render_res {
+            Ok(render_output_result) => {
+                let has_rendered = render_output_result.damage.is_some();
+                if let Some(damage) = render_output_result.damage {
+                    if let Err(err) = backend.submit(Some(&damage)) {
+                        warn!("Failed to submit buffer: {}", err);
+                    }
+                }
+                backend.window().set_cursor_visible(cursor_visible);
+
+                // Send frame events so that client start drawing their next frame
+                let time = self.clock.now();
+                post_repaint(output, &render_output_result.states, space, None, time);
+
+                if has_rendered {
+                    let mut output_presentation_feedback =
+                        take_presentation_feedback(output, space, &render_output_result.states);
+                    output_presentation_feedback.presented(
+                        time,
+                        output
+                            .current_mode()
+                            .map(|mode| Duration::from_secs_f64(1_000f64 / mode.refresh as f64))
+                            .unwrap_or_default(),
+                        0,
+                        wp_presentation_feedback::Kind::Vsync,
+                    );
+                }
+            }
+            Err(SwapBuffersError::ContextLost(err)) => {
+                error!("Critical Rendering Error: {}", err);
+                self.running.store(false, Ordering::SeqCst);
+            }
+            Err(err) => warn!("Rendering error: {}", err),
+        }
+    }
+}